@@ -1,13 +1,24 @@
-use crate::error::ZipErrorT;
+use crate::error::{Zip, ZipErrorT};
+use crate::extra_field::ExtraField;
 use crate::ffi;
-use crate::file::{Encoding, File, LocateFlag, OpenFlag as FileOpenFlag, Encryption};
+use crate::file::{
+    CompressionMethod, Encoding, Encryption, Entry, ExternalAttributes, File, LocateFlag, Location,
+    NameFlag, OpenFlag as FileOpenFlag, Stat,
+};
 use crate::source::Source;
 use crate::Error;
 use crate::Result;
+use std::any::Any;
+use std::borrow::Cow;
 use std::ffi::CStr;
 use std::marker::PhantomData;
-use std::ptr::null_mut;
+use std::mem::zeroed;
+use std::os::raw::{c_char, c_int, c_void};
+use std::panic::{self, AssertUnwindSafe};
 use std::ptr;
+use std::ptr::null_mut;
+use std::slice;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub enum OpenFlag {
@@ -18,17 +29,63 @@ pub enum OpenFlag {
     ReadOnly,
 }
 
+/// Flags that can be queried and changed on an open [Archive].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ArchiveFlag {
+    ReadOnly,
+}
+
+impl ArchiveFlag {
+    fn value(self) -> ffi::zip_flags_t {
+        match self {
+            ArchiveFlag::ReadOnly => ffi::ZIP_AFL_RDONLY,
+        }
+    }
+}
+
+/// An open zip archive.
+/// libzip reads the data of the sources given to an archive lazily, up until it is closed, so
+/// borrowed sources have to outlive the archive:
+///
+/// ```compile_fail
+/// # use is_libzip::archive::Archive;
+/// # use is_libzip::file::Encoding;
+/// # use is_libzip::source::Source;
+/// # use std::convert::TryFrom;
+/// # use std::ffi::CString;
+/// # let source = Source::try_from(Vec::new()).unwrap();
+/// let mut archive = Archive::open(source, []).unwrap();
+/// {
+///     let data = vec![0u8; 16];
+///     let source = Source::try_from(&data[..]).unwrap();
+///     archive.add(CString::new("data").unwrap(), source, Encoding::Guess, false).unwrap();
+/// }
+/// archive.close().unwrap();
+/// ```
 #[derive(Debug)]
-pub struct Archive {
+pub struct Archive<'a> {
     handle: *mut ffi::zip_t,
+    /// Owned data of the sources given to the archive, kept alive until it is closed.
+    buffers: Vec<Box<dyn Any>>,
+    /// An extra reference to the source the archive was opened from, so that it can be read back
+    /// after closing.
+    source: *mut ffi::zip_source_t,
+    /// Whether the source can be read back, for [Archive::into_bytes].
+    readable: bool,
+    /// Whether [OpenFlag::ReadOnly] was passed to [Archive::open].
+    opened_read_only: bool,
+    phantom: PhantomData<&'a ()>,
 }
 
-impl Archive {
-    pub fn open<S, F>(mut source: Source<S>, flags: F) -> Result<Archive>
+impl<'a> Archive<'a> {
+    pub fn open<S, F>(mut source: Source<S>, flags: F) -> Result<Archive<'a>>
     where
+        S: 'a,
         F: AsRef<[OpenFlag]>,
     {
         let mut flags_value = 0;
+        let opened_read_only = flags.as_ref().contains(&OpenFlag::ReadOnly);
         for flag in flags.as_ref() {
             match flag {
                 OpenFlag::CheckConsistency => flags_value |= ffi::ZIP_CHECKCONS,
@@ -47,17 +104,32 @@ impl Archive {
             if handle.is_null() {
                 Err(error.into())
             } else {
-                source.taken();
-                Ok(Archive { handle })
+                let source_handle = source.handle_mut();
+                ffi::zip_source_keep(source_handle);
+                let readable = source.is_readable();
+                let buffers = source.taken().into_iter().collect();
+                Ok(Archive {
+                    handle,
+                    buffers,
+                    source: source_handle,
+                    readable,
+                    opened_read_only,
+                    phantom: PhantomData,
+                })
             }
         }
     }
 
-    fn error(&mut self) -> ZipErrorT<&mut ffi::zip_error_t> {
-        unsafe {
-            let error = ffi::zip_get_error(self.handle);
-            (&mut *error).into()
-        }
+    /// Create an empty archive in memory.
+    /// Once everything has been added, [Archive::into_bytes] closes the archive and returns it.
+    pub fn create_in_memory() -> Result<Archive<'a>> {
+        Archive::open(Source::from_owned(Vec::new())?, [OpenFlag::Create])
+    }
+
+    /// The last error that occurred on the archive.
+    fn last_error(&self) -> Error {
+        // The error is owned by libzip, and only borrowed for as long as it takes to copy it.
+        unsafe { ZipErrorT::from(&mut *ffi::zip_get_error(self.handle)).into() }
     }
 
     /// Closes and consumes a zip file.  If this fails, an error and the failed-to-close zipfile
@@ -71,7 +143,7 @@ impl Archive {
                 self.handle = null_mut();
                 Ok(())
             } else {
-                Err(self.error().into())
+                Err(self.last_error())
             }
         }
     }
@@ -85,12 +157,34 @@ impl Archive {
         }
     }
 
+    /// Closes the archive and returns the bytes it was written as, by reading back the source it
+    /// was opened from.
+    /// This is meant for archives created with [Archive::create_in_memory] or opened from a
+    /// buffer, such as a `Source<Vec<u8>>`, which libzip replaces with the new data on close.
+    /// Sources from [Source::from_writer](crate::source::Source::from_writer) cannot be read
+    /// back, so the archive is returned with [Zip::OperationNotSupported] without closing it.
+    /// An archive without entries results in no bytes at all, as libzip removes empty archives
+    /// rather than writing them.
+    /// If closing fails, the failed-to-close archive is returned along with the error, as with
+    /// [Archive::close].  If the archive was written but reading it back fails, only the error is
+    /// returned.
+    pub fn into_bytes(mut self) -> std::result::Result<Vec<u8>, (Option<Self>, Error)> {
+        if !self.readable {
+            return Err((Some(self), zip_error(ffi::ZIP_ER_OPNOTSUPP)));
+        }
+        if let Err(e) = self.close_mut() {
+            return Err((Some(self), e));
+        }
+        unsafe { read_source(self.source) }.map_err(|e| (None, e))
+    }
+
     /// Internal non-consuming discard, to facilitate drop
     fn discard_mut(&mut self) {
         if !self.handle.is_null() {
             unsafe {
                 ffi::zip_discard(self.handle);
             }
+            self.handle = null_mut();
         }
     }
 
@@ -99,6 +193,209 @@ impl Archive {
         self.discard_mut()
     }
 
+    /// Get the current value of an archive flag.
+    pub fn flag(&self, flag: ArchiveFlag) -> Result<bool> {
+        let response = unsafe { ffi::zip_get_archive_flag(self.handle, flag.value(), 0) };
+        if response == -1 {
+            Err(self.last_error())
+        } else {
+            Ok(response != 0)
+        }
+    }
+
+    /// Set an archive flag.
+    pub fn set_flag(&mut self, flag: ArchiveFlag, value: bool) -> Result<()> {
+        let response = unsafe { ffi::zip_set_archive_flag(self.handle, flag.value(), value as _) };
+        if response == -1 {
+            Err(self.last_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Whether the archive is read-only, either because it was opened with
+    /// [OpenFlag::ReadOnly], because its source does not support writing, or because it was
+    /// marked read-only with [Archive::set_read_only].
+    pub fn is_read_only(&self) -> Result<bool> {
+        self.flag(ArchiveFlag::ReadOnly)
+    }
+
+    /// Whether the archive was opened with [OpenFlag::ReadOnly].
+    /// If the archive is read-only but this is false, the source does not support writing or the
+    /// archive was marked read-only afterwards.
+    pub fn opened_read_only(&self) -> bool {
+        self.opened_read_only
+    }
+
+    /// Mark the archive as read-only.
+    /// Marking an archive with pending changes as read-only fails, and libzip never clears the
+    /// mark once set, so clearing it fails with [Zip::ReadOnly].
+    pub fn set_read_only(&mut self, read_only: bool) -> Result<()> {
+        self.set_flag(ArchiveFlag::ReadOnly, read_only)
+    }
+
+    /// Register a callback that is called with the progress, from 0.0 to 1.0, while
+    /// [Archive::close] writes the archive.
+    /// The callback is only called when the progress has changed by at least `precision`.
+    /// It replaces any previously registered progress callback, and is dropped when the archive
+    /// is closed or discarded.
+    pub fn on_progress<F>(&mut self, precision: f64, callback: F) -> Result<()>
+    where
+        F: FnMut(f64) + 'a,
+    {
+        let state = Box::into_raw(Box::new(callback));
+        let response = unsafe {
+            ffi::zip_register_progress_callback_with_state(
+                self.handle,
+                precision,
+                Some(progress_callback::<F>),
+                Some(free_callback::<F>),
+                state as _,
+            )
+        };
+        if response == -1 {
+            unsafe { drop(Box::from_raw(state)) };
+            Err(self.last_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Register a callback that is called periodically while [Archive::close] writes the
+    /// archive.  If it returns true, closing is aborted with [Zip::Cancelled], leaving the file
+    /// on disk untouched.
+    /// It replaces any previously registered cancel callback, and is dropped when the archive is
+    /// closed or discarded.
+    pub fn on_cancel<F>(&mut self, callback: F) -> Result<()>
+    where
+        F: FnMut() -> bool + 'a,
+    {
+        let state = Box::into_raw(Box::new(callback));
+        let response = unsafe {
+            ffi::zip_register_cancel_callback_with_state(
+                self.handle,
+                Some(cancel_callback::<F>),
+                Some(free_callback::<F>),
+                state as _,
+            )
+        };
+        if response == -1 {
+            unsafe { drop(Box::from_raw(state)) };
+            Err(self.last_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The number of entries in the archive, including entries that have been added or deleted
+    /// since it was opened.
+    pub fn len(&self) -> u64 {
+        unsafe { ffi::zip_get_num_entries(self.handle, 0) as _ }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the name of the entry at the given index.
+    pub fn name<F>(&self, index: u64, flags: F) -> Result<&CStr>
+    where
+        F: AsRef<[NameFlag]>,
+    {
+        let name =
+            unsafe { ffi::zip_get_name(self.handle, index, name_flags_value(flags.as_ref())) };
+        if name.is_null() {
+            Err(self.last_error())
+        } else {
+            Ok(unsafe { CStr::from_ptr(name) })
+        }
+    }
+
+    /// Iterate over the entries in the archive.
+    /// Entries that have been deleted are skipped.
+    pub fn entries<F>(&self, flags: F) -> Entries<'_>
+    where
+        F: AsRef<[NameFlag]>,
+    {
+        let flags = name_flags_value(flags.as_ref());
+        let len = unsafe { ffi::zip_get_num_entries(self.handle, flags & ffi::ZIP_FL_UNCHANGED) };
+        Entries {
+            archive: self,
+            index: 0,
+            len: len.max(0) as _,
+            flags,
+        }
+    }
+
+    /// Find the index of the entry with the given name.
+    /// Returns `None` if there is no such entry.
+    pub fn locate<N, L>(&self, name: N, locate_flags: L) -> Result<Option<u64>>
+    where
+        N: AsRef<CStr>,
+        L: AsRef<[LocateFlag]>,
+    {
+        let index = unsafe {
+            ffi::zip_name_locate(
+                self.handle,
+                name.as_ref().as_ptr(),
+                locate_flags_value(locate_flags.as_ref()),
+            )
+        };
+        if index == -1 {
+            let error = self.last_error();
+            if error.zip() == Some(Zip::NoSuchFile) {
+                Ok(None)
+            } else {
+                Err(error)
+            }
+        } else {
+            Ok(Some(index as _))
+        }
+    }
+
+    /// Get information about the entry with the given name.
+    pub fn stat<N, F, L>(&self, name: N, flags: F, locate_flags: L) -> Result<Stat>
+    where
+        N: AsRef<CStr>,
+        F: AsRef<[NameFlag]>,
+        L: AsRef<[LocateFlag]>,
+    {
+        let flags_value =
+            name_flags_value(flags.as_ref()) | locate_flags_value(locate_flags.as_ref());
+        unsafe {
+            let mut stat = zeroed();
+            ffi::zip_stat_init(&mut stat);
+            let result = ffi::zip_stat(self.handle, name.as_ref().as_ptr(), flags_value, &mut stat);
+            if result == -1 {
+                Err(self.last_error())
+            } else {
+                Ok(Stat::from_raw(&stat))
+            }
+        }
+    }
+
+    /// Get information about the entry at the given index.
+    pub fn stat_index<F>(&self, index: u64, flags: F) -> Result<Stat>
+    where
+        F: AsRef<[NameFlag]>,
+    {
+        unsafe {
+            let mut stat = zeroed();
+            ffi::zip_stat_init(&mut stat);
+            let result = ffi::zip_stat_index(
+                self.handle,
+                index,
+                name_flags_value(flags.as_ref()),
+                &mut stat,
+            );
+            if result == -1 {
+                Err(self.last_error())
+            } else {
+                Ok(Stat::from_raw(&stat))
+            }
+        }
+    }
+
     /// Add a file to the zip archive.
     /// Returns the index of the new file.
     pub fn add<N, S>(
@@ -110,12 +407,9 @@ impl Archive {
     ) -> Result<u64>
     where
         N: AsRef<CStr>,
+        S: 'a,
     {
-        let mut flags = match encoding {
-            Encoding::Guess => ffi::ZIP_FL_ENC_GUESS,
-            Encoding::Utf8 => ffi::ZIP_FL_ENC_UTF_8,
-            Encoding::Cp437 => ffi::ZIP_FL_ENC_CP437,
-        };
+        let mut flags = encoding_flags_value(encoding);
         if overwrite {
             flags |= ffi::ZIP_FL_OVERWRITE;
         }
@@ -129,69 +423,562 @@ impl Archive {
             )
         };
         if response == -1 {
-            Err(self.error().into())
+            Err(self.last_error())
+        } else {
+            self.buffers.extend(source.taken());
+            Ok(response as _)
+        }
+    }
+
+    /// Add a directory to the zip archive.
+    /// Returns the index of the new directory entry.
+    pub fn add_dir<N>(&mut self, name: N, encoding: Encoding) -> Result<u64>
+    where
+        N: AsRef<CStr>,
+    {
+        let response = unsafe {
+            ffi::zip_dir_add(
+                self.handle,
+                name.as_ref().as_ptr(),
+                encoding_flags_value(encoding),
+            )
+        };
+        if response == -1 {
+            Err(self.last_error())
         } else {
-            source.taken();
             Ok(response as _)
         }
     }
 
     /// Replace a file in the zip archive.
-    pub fn replace<S>(&mut self, index: u64, mut source: Source<S>) -> Result<()> {
+    pub fn replace<S>(&mut self, index: u64, mut source: Source<S>) -> Result<()>
+    where
+        S: 'a,
+    {
         let response =
             unsafe { ffi::zip_file_replace(self.handle, index as _, source.handle_mut(), 0) };
         if response == -1 {
-            Err(self.error().into())
+            Err(self.last_error())
         } else {
-            source.taken();
+            self.buffers.extend(source.taken());
             Ok(())
         }
     }
 
-    // Set encryption flag for a file.
-    pub fn set_encryption_on_file(&mut self, encryption: Encryption, file_index: u64 ) -> Result<()> {
-        let mode = match encryption {
-            Encryption::None => ffi::ZIP_EM_NONE,
-            Encryption::AES128 => ffi::ZIP_EM_AES_128,
-            Encryption::AES192 => ffi::ZIP_EM_AES_192,
-            Encryption::AES256 => ffi::ZIP_EM_AES_256,
-            Encryption::PkWare => ffi::ZIP_EM_TRAD_PKWARE,
+    /// Delete a file from the zip archive.
+    pub fn delete(&mut self, index: u64) -> Result<()> {
+        let response = unsafe { ffi::zip_delete(self.handle, index) };
+        if response == -1 {
+            Err(self.last_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Rename a file in the zip archive.
+    pub fn rename<N>(&mut self, index: u64, new_name: N, encoding: Encoding) -> Result<()>
+    where
+        N: AsRef<CStr>,
+    {
+        let response = unsafe {
+            ffi::zip_file_rename(
+                self.handle,
+                index,
+                new_name.as_ref().as_ptr(),
+                encoding_flags_value(encoding),
+            )
         };
+        if response == -1 {
+            Err(self.last_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Revert all changes made to a file in the zip archive.
+    pub fn unchange(&mut self, index: u64) -> Result<()> {
+        let response = unsafe { ffi::zip_unchange(self.handle, index) };
+        if response == -1 {
+            Err(self.last_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Revert all changes made to the zip archive, both to its files and to the archive itself.
+    pub fn unchange_all(&mut self) -> Result<()> {
+        let response = unsafe { ffi::zip_unchange_all(self.handle) };
+        if response == -1 {
+            Err(self.last_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Revert all changes made to the zip archive itself, such as its comment, leaving changes to
+    /// its files intact.
+    pub fn unchange_archive(&mut self) -> Result<()> {
+        let response = unsafe { ffi::zip_unchange_archive(self.handle) };
+        if response == -1 {
+            Err(self.last_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Set the compression method for a file.
+    /// The level is interpreted by the selected method, as the valid levels differ between
+    /// methods, with 0 meaning the default level for the method.
+    pub fn set_compression(
+        &mut self,
+        index: u64,
+        method: CompressionMethod,
+        level: u32,
+    ) -> Result<()> {
+        let response =
+            unsafe { ffi::zip_set_file_compression(self.handle, index, method.value(), level) };
+        if response == -1 {
+            Err(self.last_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Get the comment of the zip archive.
+    pub fn comment<F>(&self, flags: F) -> Result<&[u8]>
+    where
+        F: AsRef<[NameFlag]>,
+    {
+        let mut len: c_int = 0;
+        let comment = unsafe {
+            ffi::zip_get_archive_comment(self.handle, &mut len, name_flags_value(flags.as_ref()))
+        };
+        if comment.is_null() {
+            Err(self.last_error())
+        } else {
+            Ok(unsafe { slice::from_raw_parts(comment as _, len as _) })
+        }
+    }
+
+    /// Get the comment of the zip archive as a string, decoded with the given encoding.
+    /// Invalid UTF-8 is replaced, as with [String::from_utf8_lossy].
+    pub fn comment_str(&self, encoding: Encoding) -> Result<Cow<'_, str>> {
+        let mut len: c_int = 0;
+        let comment = unsafe {
+            ffi::zip_get_archive_comment(self.handle, &mut len, decode_flags_value(encoding))
+        };
+        if comment.is_null() {
+            Err(self.last_error())
+        } else {
+            let comment = unsafe { slice::from_raw_parts(comment as _, len as _) };
+            Ok(String::from_utf8_lossy(comment))
+        }
+    }
+
+    /// Set the comment of the zip archive.  An empty comment removes it.
+    pub fn set_comment<C>(&mut self, comment: C) -> Result<()>
+    where
+        C: AsRef<[u8]>,
+    {
+        let comment = comment.as_ref();
+        if comment.len() > u16::MAX as usize {
+            return Err(zip_error(ffi::ZIP_ER_INVAL));
+        }
+        let response = unsafe {
+            ffi::zip_set_archive_comment(self.handle, comment.as_ptr() as _, comment.len() as _)
+        };
+        if response == -1 {
+            Err(self.last_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Get the comment of a file in the zip archive.
+    pub fn entry_comment<F>(&self, index: u64, flags: F) -> Result<&[u8]>
+    where
+        F: AsRef<[NameFlag]>,
+    {
+        let mut len: u32 = 0;
+        let comment = unsafe {
+            ffi::zip_file_get_comment(
+                self.handle,
+                index,
+                &mut len,
+                name_flags_value(flags.as_ref()),
+            )
+        };
+        if comment.is_null() {
+            Err(self.last_error())
+        } else {
+            Ok(unsafe { slice::from_raw_parts(comment as _, len as _) })
+        }
+    }
+
+    /// Get the comment of a file in the zip archive as a string, decoded with the given encoding.
+    /// Invalid UTF-8 is replaced, as with [String::from_utf8_lossy].
+    pub fn entry_comment_str(&self, index: u64, encoding: Encoding) -> Result<Cow<'_, str>> {
+        let mut len: u32 = 0;
+        let comment = unsafe {
+            ffi::zip_file_get_comment(self.handle, index, &mut len, decode_flags_value(encoding))
+        };
+        if comment.is_null() {
+            Err(self.last_error())
+        } else {
+            let comment = unsafe { slice::from_raw_parts(comment as _, len as _) };
+            Ok(String::from_utf8_lossy(comment))
+        }
+    }
+
+    /// Set the comment of a file in the zip archive.  An empty comment removes it.
+    pub fn set_entry_comment<C>(&mut self, index: u64, comment: C, encoding: Encoding) -> Result<()>
+    where
+        C: AsRef<[u8]>,
+    {
+        let comment = comment.as_ref();
+        if comment.len() > u16::MAX as usize {
+            return Err(zip_error(ffi::ZIP_ER_INVAL));
+        }
         let response = unsafe {
-            ffi::zip_file_set_encryption(
+            ffi::zip_file_set_comment(
                 self.handle,
-                file_index,
-                mode as _,
-                ptr::null::<i8>(),
+                index,
+                comment.as_ptr() as _,
+                comment.len() as _,
+                encoding_flags_value(encoding),
             )
         };
         if response == -1 {
-            Err(self.error().into())
+            Err(self.last_error())
         } else {
             Ok(())
         }
     }
 
-    // set archive default encryption password
-    pub fn set_encryption_password<N>(&mut self, password: N) -> Result<()>
-        where N: AsRef<CStr>
+    /// Set the modification time of a file in the zip archive.
+    pub fn set_mtime(&mut self, index: u64, mtime: SystemTime) -> Result<()> {
+        let mtime = match mtime.duration_since(UNIX_EPOCH) {
+            Ok(duration) => duration.as_secs() as i64,
+            Err(error) => -(error.duration().as_secs() as i64),
+        };
+        let response = unsafe { ffi::zip_file_set_mtime(self.handle, index, mtime as _, 0) };
+        if response == -1 {
+            Err(self.last_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Set the modification time of a file in the zip archive, in MS-DOS format.
+    pub fn set_dos_time(&mut self, index: u64, time: u16, date: u16) -> Result<()> {
+        let response = unsafe { ffi::zip_file_set_dostime(self.handle, index, time, date, 0) };
+        if response == -1 {
+            Err(self.last_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Get the external attributes of a file in the zip archive.
+    pub fn external_attributes<F>(&self, index: u64, flags: F) -> Result<ExternalAttributes>
+    where
+        F: AsRef<[NameFlag]>,
     {
+        let mut opsys = 0;
+        let mut attributes = 0;
+        let response = unsafe {
+            ffi::zip_file_get_external_attributes(
+                self.handle,
+                index,
+                name_flags_value(flags.as_ref()),
+                &mut opsys,
+                &mut attributes,
+            )
+        };
+        if response == -1 {
+            Err(self.last_error())
+        } else {
+            Ok(ExternalAttributes {
+                opsys: opsys.into(),
+                attributes,
+            })
+        }
+    }
+
+    /// Set the external attributes of a file in the zip archive.
+    pub fn set_external_attributes(
+        &mut self,
+        index: u64,
+        attributes: ExternalAttributes,
+    ) -> Result<()> {
         let response = unsafe {
-            ffi::zip_set_default_password(
+            ffi::zip_file_set_external_attributes(
                 self.handle,
-                password.as_ref().as_ptr()
+                index,
+                0,
+                attributes.opsys.value(),
+                attributes.attributes,
             )
         };
         if response == -1 {
-            Err(self.error().into())
+            Err(self.last_error())
         } else {
             Ok(())
         }
     }
 
+    /// The number of extra fields of a file in the zip archive.
+    pub fn extra_fields_count(&self, index: u64, location: Location) -> Result<u16> {
+        let count =
+            unsafe { ffi::zip_file_extra_fields_count(self.handle, index, location.value()) };
+        if count == -1 {
+            Err(self.last_error())
+        } else {
+            Ok(count as _)
+        }
+    }
 
-    /// Add a file to the zip archive.
-    /// Returns the index of the new file.
+    /// The number of extra fields with the given id of a file in the zip archive.
+    pub fn extra_fields_count_by_id(&self, index: u64, id: u16, location: Location) -> Result<u16> {
+        let count = unsafe {
+            ffi::zip_file_extra_fields_count_by_id(self.handle, index, id, location.value())
+        };
+        if count == -1 {
+            Err(self.last_error())
+        } else {
+            Ok(count as _)
+        }
+    }
+
+    /// Iterate over the extra fields of a file in the zip archive, as (id, data) pairs.
+    pub fn extra_fields(&self, index: u64, location: Location) -> Result<ExtraFields<'_>> {
+        let len = self.extra_fields_count(index, location)?;
+        Ok(ExtraFields {
+            archive: self,
+            index,
+            extra_field_index: 0,
+            len,
+            location,
+        })
+    }
+
+    /// Get the data of the extra field with the given id of a file in the zip archive.
+    /// The extra field index counts only the extra fields with that id.
+    pub fn extra_field_by_id(
+        &self,
+        index: u64,
+        id: u16,
+        extra_field_index: u16,
+        location: Location,
+    ) -> Result<&[u8]> {
+        let mut len = 0;
+        let data = unsafe {
+            ffi::zip_file_extra_field_get_by_id(
+                self.handle,
+                index,
+                id,
+                extra_field_index,
+                &mut len,
+                location.value(),
+            )
+        };
+        if data.is_null() {
+            Err(self.last_error())
+        } else {
+            Ok(unsafe { slice::from_raw_parts(data, len as _) })
+        }
+    }
+
+    /// Replace the data of an extra field of a file in the zip archive.
+    /// The extra field index counts only the extra fields with the given id.
+    pub fn set_extra_field<D>(
+        &mut self,
+        index: u64,
+        id: u16,
+        extra_field_index: u16,
+        data: D,
+        location: Location,
+    ) -> Result<()>
+    where
+        D: AsRef<[u8]>,
+    {
+        let data = data.as_ref();
+        if data.len() > u16::MAX as usize {
+            return Err(zip_error(ffi::ZIP_ER_INVAL));
+        }
+        let response = unsafe {
+            ffi::zip_file_extra_field_set(
+                self.handle,
+                index,
+                id,
+                extra_field_index,
+                data.as_ptr(),
+                data.len() as _,
+                location.value(),
+            )
+        };
+        if response == -1 {
+            Err(self.last_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Add a new extra field to a file in the zip archive.
+    pub fn add_extra_field<D>(
+        &mut self,
+        index: u64,
+        id: u16,
+        data: D,
+        location: Location,
+    ) -> Result<()>
+    where
+        D: AsRef<[u8]>,
+    {
+        self.set_extra_field(index, id, ffi::ZIP_EXTRA_FIELD_NEW as _, data, location)
+    }
+
+    /// Delete an extra field of a file in the zip archive.
+    /// The extra field index counts all extra fields, as with [Archive::extra_fields].
+    pub fn delete_extra_field(
+        &mut self,
+        index: u64,
+        extra_field_index: u16,
+        location: Location,
+    ) -> Result<()> {
+        let response = unsafe {
+            ffi::zip_file_extra_field_delete(
+                self.handle,
+                index,
+                extra_field_index,
+                location.value(),
+            )
+        };
+        if response == -1 {
+            Err(self.last_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Delete extra fields with the given id of a file in the zip archive.
+    /// The extra field index counts only the extra fields with that id; `None` deletes all of
+    /// them.
+    pub fn delete_extra_field_by_id(
+        &mut self,
+        index: u64,
+        id: u16,
+        extra_field_index: Option<u16>,
+        location: Location,
+    ) -> Result<()> {
+        let extra_field_index = extra_field_index.unwrap_or(ffi::ZIP_EXTRA_FIELD_ALL as _);
+        let response = unsafe {
+            ffi::zip_file_extra_field_delete_by_id(
+                self.handle,
+                index,
+                id,
+                extra_field_index,
+                location.value(),
+            )
+        };
+        if response == -1 {
+            Err(self.last_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Get the first extra field of the given type of a file in the zip archive.
+    /// Returns `None` if the file has no such extra field.
+    pub fn typed_extra_field<T>(&self, index: u64, location: Location) -> Result<Option<T>>
+    where
+        T: ExtraField,
+    {
+        if self.extra_fields_count_by_id(index, T::ID, location)? == 0 {
+            return Ok(None);
+        }
+        let data = self.extra_field_by_id(index, T::ID, 0, location)?;
+        match T::decode(data) {
+            Some(field) => Ok(Some(field)),
+            None => Err(zip_error(ffi::ZIP_ER_INCONS)),
+        }
+    }
+
+    /// Set the first extra field of the given type of a file in the zip archive, adding it if
+    /// the file has no such extra field yet.
+    pub fn set_typed_extra_field<T>(
+        &mut self,
+        index: u64,
+        field: &T,
+        location: Location,
+    ) -> Result<()>
+    where
+        T: ExtraField,
+    {
+        let extra_field_index = if self.extra_fields_count_by_id(index, T::ID, location)? == 0 {
+            ffi::ZIP_EXTRA_FIELD_NEW as _
+        } else {
+            0
+        };
+        self.set_extra_field(index, T::ID, extra_field_index, field.encode(), location)
+    }
+
+    // Set encryption flag for a file, using the archive default password.
+    pub fn set_encryption_on_file(
+        &mut self,
+        encryption: Encryption,
+        file_index: u64,
+    ) -> Result<()> {
+        self.set_file_encryption(encryption, file_index, ptr::null())
+    }
+
+    /// Set encryption flag for a file, with a password for this file only.
+    pub fn set_encryption_with_password<N>(
+        &mut self,
+        encryption: Encryption,
+        file_index: u64,
+        password: N,
+    ) -> Result<()>
+    where
+        N: AsRef<CStr>,
+    {
+        self.set_file_encryption(encryption, file_index, password.as_ref().as_ptr())
+    }
+
+    fn set_file_encryption(
+        &mut self,
+        encryption: Encryption,
+        file_index: u64,
+        password: *const c_char,
+    ) -> Result<()> {
+        if !encryption.is_supported(true) {
+            return Err(zip_error(ffi::ZIP_ER_ENCRNOTSUPP));
+        }
+        let response = unsafe {
+            ffi::zip_file_set_encryption(self.handle, file_index, encryption.value(), password)
+        };
+        if response == -1 {
+            Err(self.last_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    // set archive default encryption password
+    pub fn set_encryption_password<N>(&mut self, password: N) -> Result<()>
+    where
+        N: AsRef<CStr>,
+    {
+        let response =
+            unsafe { ffi::zip_set_default_password(self.handle, password.as_ref().as_ptr()) };
+        if response == -1 {
+            Err(self.last_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Open a file in the zip archive for reading.
     pub fn open_file<N, O, L>(
         &mut self,
         name: N,
@@ -203,26 +990,78 @@ impl Archive {
         O: AsRef<[FileOpenFlag]>,
         L: AsRef<[LocateFlag]>,
     {
-        let mut flags_value = 0;
-        for flag in open_flags.as_ref() {
-            match flag {
-                FileOpenFlag::Compressed => flags_value |= ffi::ZIP_FL_COMPRESSED,
-                FileOpenFlag::Unchanged => flags_value |= ffi::ZIP_FL_UNCHANGED,
-            }
-        }
-        for flag in locate_flags.as_ref() {
-            match flag {
-                LocateFlag::NoCase => flags_value |= ffi::ZIP_FL_NOCASE,
-                LocateFlag::NoDir => flags_value |= ffi::ZIP_FL_NODIR,
-                LocateFlag::EncodingRaw => flags_value |= ffi::ZIP_FL_ENC_RAW,
-                LocateFlag::EncodingGuess => flags_value |= ffi::ZIP_FL_ENC_GUESS,
-                LocateFlag::EncodingStrict => flags_value |= ffi::ZIP_FL_ENC_STRICT,
-            }
-        }
-        let handle =
-            unsafe { ffi::zip_fopen(self.handle, name.as_ref().as_ptr(), flags_value as _) };
+        let flags_value =
+            open_flags_value(open_flags.as_ref()) | locate_flags_value(locate_flags.as_ref());
+        let handle = unsafe { ffi::zip_fopen(self.handle, name.as_ref().as_ptr(), flags_value) };
+        self.file(handle)
+    }
+
+    /// Open an encrypted file in the zip archive for reading, using the given password instead of
+    /// the archive's default password.
+    pub fn open_file_encrypted<N, O, L, P>(
+        &mut self,
+        name: N,
+        open_flags: O,
+        locate_flags: L,
+        password: P,
+    ) -> Result<File<'_>>
+    where
+        N: AsRef<CStr>,
+        O: AsRef<[FileOpenFlag]>,
+        L: AsRef<[LocateFlag]>,
+        P: AsRef<CStr>,
+    {
+        let flags_value =
+            open_flags_value(open_flags.as_ref()) | locate_flags_value(locate_flags.as_ref());
+        let handle = unsafe {
+            ffi::zip_fopen_encrypted(
+                self.handle,
+                name.as_ref().as_ptr(),
+                flags_value,
+                password.as_ref().as_ptr(),
+            )
+        };
+        self.file(handle)
+    }
+
+    /// Open the file at the given index in the zip archive for reading.
+    pub fn open_index<O>(&mut self, index: u64, open_flags: O) -> Result<File<'_>>
+    where
+        O: AsRef<[FileOpenFlag]>,
+    {
+        let handle = unsafe {
+            ffi::zip_fopen_index(self.handle, index, open_flags_value(open_flags.as_ref()))
+        };
+        self.file(handle)
+    }
+
+    /// Open the encrypted file at the given index in the zip archive for reading, using the given
+    /// password instead of the archive's default password.
+    pub fn open_index_encrypted<O, P>(
+        &mut self,
+        index: u64,
+        open_flags: O,
+        password: P,
+    ) -> Result<File<'_>>
+    where
+        O: AsRef<[FileOpenFlag]>,
+        P: AsRef<CStr>,
+    {
+        let handle = unsafe {
+            ffi::zip_fopen_index_encrypted(
+                self.handle,
+                index,
+                open_flags_value(open_flags.as_ref()),
+                password.as_ref().as_ptr(),
+            )
+        };
+        self.file(handle)
+    }
+
+    /// Wrap a handle returned by one of the zip_fopen functions.
+    fn file(&mut self, handle: *mut ffi::zip_file_t) -> Result<File<'_>> {
         if handle.is_null() {
-            Err(self.error().into())
+            Err(self.last_error())
         } else {
             Ok(File {
                 handle,
@@ -235,10 +1074,206 @@ impl Archive {
 /// Closes the archive, silently discarding on error.
 /// It's strongly recommended to use the [Archive::close] method instead and validate that no
 /// errors have occurred.
-impl Drop for Archive {
+impl Drop for Archive<'_> {
     fn drop(&mut self) {
-        if let Err(_) = self.close_mut() {
+        if self.close_mut().is_err() {
             self.discard_mut()
         }
+        // The source may read from the buffers, so it has to be freed before them.
+        unsafe { ffi::zip_source_free(self.source) };
+    }
+}
+
+/// Read the whole data of a source.
+unsafe fn read_source(source: *mut ffi::zip_source_t) -> Result<Vec<u8>> {
+    const CHUNK_SIZE: usize = 64 * 1024;
+
+    if ffi::zip_source_open(source) < 0 {
+        let error: Error = ZipErrorT::from(&mut *ffi::zip_source_error(source)).into();
+        // Closing an archive whose entries were all deleted removes the data.
+        return if error.zip() == Some(Zip::Deleted) {
+            Ok(Vec::new())
+        } else {
+            Err(error)
+        };
+    }
+    let mut bytes: Vec<u8> = Vec::new();
+    let result = loop {
+        bytes.reserve(CHUNK_SIZE);
+        let len = bytes.len();
+        let read = ffi::zip_source_read(
+            source,
+            bytes.as_mut_ptr().add(len) as _,
+            (bytes.capacity() - len) as _,
+        );
+        match read {
+            0 => break Ok(bytes),
+            read if read < 0 => {
+                break Err(ZipErrorT::from(&mut *ffi::zip_source_error(source)).into());
+            }
+            read => bytes.set_len(len + read as usize),
+        }
+    };
+    ffi::zip_source_close(source);
+    result
+}
+
+/// Iterator over the entries of an [Archive], created by [Archive::entries].
+#[derive(Debug)]
+pub struct Entries<'a> {
+    archive: &'a Archive<'a>,
+    index: u64,
+    len: u64,
+    flags: ffi::zip_flags_t,
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = Result<Entry<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.index < self.len {
+            let index = self.index;
+            self.index += 1;
+            let name = unsafe { ffi::zip_get_name(self.archive.handle, index, self.flags) };
+            if name.is_null() {
+                let error = self.archive.last_error();
+                if error.zip() == Some(Zip::Deleted) {
+                    continue;
+                }
+                return Some(Err(error));
+            }
+            return Some(Ok(Entry {
+                index,
+                name: unsafe { CStr::from_ptr(name) },
+            }));
+        }
+        None
+    }
+}
+
+unsafe extern "C" fn progress_callback<F>(_: *mut ffi::zip_t, progress: f64, state: *mut c_void)
+where
+    F: FnMut(f64),
+{
+    let callback = &mut *(state as *mut F);
+    // Unwinding into libzip is undefined behavior, and there is nothing to report a panic to.
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| callback(progress)));
+}
+
+unsafe extern "C" fn cancel_callback<F>(_: *mut ffi::zip_t, state: *mut c_void) -> c_int
+where
+    F: FnMut() -> bool,
+{
+    let callback = &mut *(state as *mut F);
+    // A panicking callback cancels, rather than unwinding into libzip.
+    panic::catch_unwind(AssertUnwindSafe(callback)).unwrap_or(true) as _
+}
+
+/// Drop a callback boxed by [Archive::on_progress] or [Archive::on_cancel], when libzip releases
+/// it.
+unsafe extern "C" fn free_callback<F>(state: *mut c_void) {
+    // A panicking Drop of a captured value must not unwind into libzip either, and there is
+    // nowhere to report it to.
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(state as *mut F))));
+}
+
+/// Build an error from a libzip error code.
+fn zip_error(code: u32) -> Error {
+    let error: ZipErrorT<_> = (code as c_int).into();
+    error.into()
+}
+
+/// Flags to have libzip decode a stored string as UTF-8.
+/// Guess lets libzip detect the encoding, Utf8 returns the raw bytes, and Cp437 follows the zip
+/// specification, which treats strings without the UTF-8 flag as CP437.
+fn decode_flags_value(encoding: Encoding) -> ffi::zip_flags_t {
+    match encoding {
+        Encoding::Guess => ffi::ZIP_FL_ENC_GUESS,
+        Encoding::Utf8 => ffi::ZIP_FL_ENC_RAW,
+        Encoding::Cp437 => ffi::ZIP_FL_ENC_STRICT,
+    }
+}
+
+fn encoding_flags_value(encoding: Encoding) -> ffi::zip_flags_t {
+    match encoding {
+        Encoding::Guess => ffi::ZIP_FL_ENC_GUESS,
+        Encoding::Utf8 => ffi::ZIP_FL_ENC_UTF_8,
+        Encoding::Cp437 => ffi::ZIP_FL_ENC_CP437,
+    }
+}
+
+/// Iterator over the extra fields of a file in an [Archive], created by [Archive::extra_fields].
+#[derive(Debug)]
+pub struct ExtraFields<'a> {
+    archive: &'a Archive<'a>,
+    index: u64,
+    extra_field_index: u16,
+    len: u16,
+    location: Location,
+}
+
+impl<'a> Iterator for ExtraFields<'a> {
+    type Item = Result<(u16, &'a [u8])>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.extra_field_index >= self.len {
+            return None;
+        }
+        let mut id = 0;
+        let mut len = 0;
+        let data = unsafe {
+            ffi::zip_file_extra_field_get(
+                self.archive.handle,
+                self.index,
+                self.extra_field_index,
+                &mut id,
+                &mut len,
+                self.location.value(),
+            )
+        };
+        self.extra_field_index += 1;
+        if data.is_null() {
+            Some(Err(self.archive.last_error()))
+        } else {
+            Some(Ok((id, unsafe { slice::from_raw_parts(data, len as _) })))
+        }
+    }
+}
+
+fn name_flags_value(flags: &[NameFlag]) -> ffi::zip_flags_t {
+    let mut flags_value = 0;
+    for flag in flags {
+        match flag {
+            NameFlag::Unchanged => flags_value |= ffi::ZIP_FL_UNCHANGED,
+            NameFlag::EncodingRaw => flags_value |= ffi::ZIP_FL_ENC_RAW,
+            NameFlag::EncodingGuess => flags_value |= ffi::ZIP_FL_ENC_GUESS,
+            NameFlag::EncodingStrict => flags_value |= ffi::ZIP_FL_ENC_STRICT,
+        }
+    }
+    flags_value
+}
+
+fn open_flags_value(flags: &[FileOpenFlag]) -> ffi::zip_flags_t {
+    let mut flags_value = 0;
+    for flag in flags {
+        match flag {
+            FileOpenFlag::Compressed => flags_value |= ffi::ZIP_FL_COMPRESSED,
+            FileOpenFlag::Unchanged => flags_value |= ffi::ZIP_FL_UNCHANGED,
+        }
+    }
+    flags_value
+}
+
+fn locate_flags_value(flags: &[LocateFlag]) -> ffi::zip_flags_t {
+    let mut flags_value = 0;
+    for flag in flags {
+        match flag {
+            LocateFlag::NoCase => flags_value |= ffi::ZIP_FL_NOCASE,
+            LocateFlag::NoDir => flags_value |= ffi::ZIP_FL_NODIR,
+            LocateFlag::EncodingRaw => flags_value |= ffi::ZIP_FL_ENC_RAW,
+            LocateFlag::EncodingGuess => flags_value |= ffi::ZIP_FL_ENC_GUESS,
+            LocateFlag::EncodingStrict => flags_value |= ffi::ZIP_FL_ENC_STRICT,
+        }
     }
+    flags_value
 }
@@ -15,6 +15,7 @@ pub enum System {
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Zip {
+    Cancelled,
     Changed,
     Close,
     CompressionNotSupported,
@@ -56,6 +57,22 @@ pub struct Error {
     message: String,
 }
 
+impl Error {
+    /// The system error that caused this error, if any.
+    pub fn system(&self) -> Option<System> {
+        self.system
+    }
+
+    /// The libzip error code, if any.
+    pub fn zip(&self) -> Option<Zip> {
+        self.zip
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "Error: {}", self.message)
@@ -131,6 +148,7 @@ where
     pub fn zip(&self) -> Option<Zip> {
         let code = unsafe { ffi::zip_error_code_zip(self.deref()) };
         Some(match code as _ {
+            ffi::ZIP_ER_CANCELLED => Zip::Cancelled,
             ffi::ZIP_ER_CHANGED => Zip::Changed,
             ffi::ZIP_ER_CLOSE => Zip::Close,
             ffi::ZIP_ER_COMPNOTSUPP => Zip::CompressionNotSupported,
@@ -0,0 +1,393 @@
+//! Typed decoders and encoders for well-known extra fields, for use with
+//! [Archive::typed_extra_field](crate::archive::Archive::typed_extra_field) and
+//! [Archive::set_typed_extra_field](crate::archive::Archive::set_typed_extra_field).
+//! All values are stored little-endian, as in the rest of the zip format.
+//! libzip manages the Zip64, Unicode path, Unicode comment and WinZip AES extra fields itself,
+//! hiding them from the extra field accessors and refusing to set them.  Their types therefore
+//! do not implement [ExtraField], and only apply to raw bytes obtained elsewhere.
+
+use std::convert::TryInto;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// An extra field with a well-known id and layout.
+pub trait ExtraField: Sized {
+    /// The header id of the extra field.
+    const ID: u16;
+
+    /// Parse the data of the extra field, returning `None` if it is malformed.
+    fn decode(data: &[u8]) -> Option<Self>;
+
+    /// Serialize the extra field into its data, without the id and length header.
+    fn encode(&self) -> Vec<u8>;
+}
+
+/// A little-endian cursor over extra field data.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            None
+        } else {
+            let (bytes, rest) = self.data.split_at(len);
+            self.data = rest;
+            Some(bytes)
+        }
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|bytes| bytes[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes(2)
+            .map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.bytes(8)
+            .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+/// Convert Unix seconds to a [SystemTime].
+fn from_unix(seconds: i64) -> SystemTime {
+    if seconds >= 0 {
+        UNIX_EPOCH + Duration::from_secs(seconds as _)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(seconds.unsigned_abs())
+    }
+}
+
+/// Convert a [SystemTime] to Unix seconds, truncated to the 32 bits the zip format allows.
+fn to_unix(time: SystemTime) -> i32 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as _,
+        Err(error) => -(error.duration().as_secs() as i64) as _,
+    }
+}
+
+/// Seconds between the Windows FILETIME epoch (1601-01-01) and the Unix epoch.
+const FILETIME_UNIX_OFFSET: u64 = 11_644_473_600;
+const FILETIME_TICKS_PER_SECOND: u64 = 10_000_000;
+
+/// Convert a Windows FILETIME, in 100 nanosecond ticks since 1601, to a [SystemTime].
+fn from_filetime(ticks: u64) -> SystemTime {
+    let duration = Duration::new(
+        ticks / FILETIME_TICKS_PER_SECOND,
+        (ticks % FILETIME_TICKS_PER_SECOND * 100) as _,
+    );
+    let offset = Duration::from_secs(FILETIME_UNIX_OFFSET);
+    if duration >= offset {
+        UNIX_EPOCH + (duration - offset)
+    } else {
+        UNIX_EPOCH - (offset - duration)
+    }
+}
+
+/// Convert a [SystemTime] to a Windows FILETIME, saturating at its epoch and at its maximum.
+fn to_filetime(time: SystemTime) -> u64 {
+    let offset = Duration::from_secs(FILETIME_UNIX_OFFSET);
+    let duration = match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => offset + duration,
+        Err(error) => offset.checked_sub(error.duration()).unwrap_or_default(),
+    };
+    duration
+        .as_secs()
+        .checked_mul(FILETIME_TICKS_PER_SECOND)
+        .and_then(|ticks| ticks.checked_add(duration.subsec_nanos() as u64 / 100))
+        .unwrap_or(u64::MAX)
+}
+
+/// The extended timestamp extra field (0x5455).
+/// The central directory version usually holds only the modification time, even when the flags
+/// announce the others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExtendedTimestamp {
+    pub mtime: Option<SystemTime>,
+    pub atime: Option<SystemTime>,
+    pub ctime: Option<SystemTime>,
+}
+
+impl ExtraField for ExtendedTimestamp {
+    const ID: u16 = 0x5455;
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader { data };
+        let flags = reader.u8()?;
+        let mut times = [None; 3];
+        for (bit, time) in times.iter_mut().enumerate() {
+            if flags & (1 << bit) != 0 && !reader.is_empty() {
+                *time = Some(from_unix(reader.u32()? as i32 as _));
+            }
+        }
+        let [mtime, atime, ctime] = times;
+        Some(ExtendedTimestamp {
+            mtime,
+            atime,
+            ctime,
+        })
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut data = vec![0];
+        for (bit, time) in [self.mtime, self.atime, self.ctime].iter().enumerate() {
+            if let Some(time) = time {
+                data[0] |= 1 << bit;
+                data.extend_from_slice(&to_unix(*time).to_le_bytes());
+            }
+        }
+        data
+    }
+}
+
+/// The Info-ZIP Unix extra field (0x7875), holding the owner's user and group ids.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InfoZipUnix {
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl InfoZipUnix {
+    /// Read a variable-sized id, which must fit in 32 bits.
+    fn read_id(reader: &mut Reader<'_>) -> Option<u32> {
+        let size = reader.u8()? as usize;
+        let bytes = reader.bytes(size)?;
+        let mut id: u64 = 0;
+        for (i, byte) in bytes.iter().enumerate() {
+            if *byte != 0 {
+                if i >= 8 {
+                    return None;
+                }
+                id |= (*byte as u64) << (i * 8);
+            }
+        }
+        id.try_into().ok()
+    }
+}
+
+impl ExtraField for InfoZipUnix {
+    const ID: u16 = 0x7875;
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader { data };
+        if reader.u8()? != 1 {
+            return None;
+        }
+        let uid = InfoZipUnix::read_id(&mut reader)?;
+        let gid = InfoZipUnix::read_id(&mut reader)?;
+        Some(InfoZipUnix { uid, gid })
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut data = vec![1, 4];
+        data.extend_from_slice(&self.uid.to_le_bytes());
+        data.push(4);
+        data.extend_from_slice(&self.gid.to_le_bytes());
+        data
+    }
+}
+
+/// The NTFS extra field (0x000a), holding high resolution timestamps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ntfs {
+    pub mtime: SystemTime,
+    pub atime: SystemTime,
+    pub ctime: SystemTime,
+}
+
+impl Ntfs {
+    const TIMESTAMPS_TAG: u16 = 0x0001;
+}
+
+impl ExtraField for Ntfs {
+    const ID: u16 = 0x000a;
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader { data };
+        let _reserved = reader.u32()?;
+        while !reader.is_empty() {
+            let tag = reader.u16()?;
+            let size = reader.u16()? as usize;
+            let mut attribute = Reader {
+                data: reader.bytes(size)?,
+            };
+            if tag == Ntfs::TIMESTAMPS_TAG {
+                return Some(Ntfs {
+                    mtime: from_filetime(attribute.u64()?),
+                    atime: from_filetime(attribute.u64()?),
+                    ctime: from_filetime(attribute.u64()?),
+                });
+            }
+        }
+        None
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut data = vec![0; 4];
+        data.extend_from_slice(&Ntfs::TIMESTAMPS_TAG.to_le_bytes());
+        data.extend_from_slice(&24u16.to_le_bytes());
+        for time in [self.mtime, self.atime, self.ctime].iter() {
+            data.extend_from_slice(&to_filetime(*time).to_le_bytes());
+        }
+        data
+    }
+}
+
+/// The Zip64 extended information extra field (0x0001).
+/// Each value is only present if the corresponding value in the header is saturated, and the
+/// values that are present appear in this order.  Which values the data holds therefore depends
+/// on the header, so this does not implement [ExtraField]; use [Zip64::decode_with] instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Zip64 {
+    pub size: Option<u64>,
+    pub compressed_size: Option<u64>,
+    pub header_offset: Option<u64>,
+    pub disk_start: Option<u32>,
+}
+
+impl Zip64 {
+    /// The header id of the extra field.
+    pub const ID: u16 = 0x0001;
+
+    /// Parse the data of the extra field, given which values of the header are saturated
+    /// (0xffffffff, or 0xffff for the disk number), returning `None` if it is malformed.
+    pub fn decode_with(
+        data: &[u8],
+        size_saturated: bool,
+        compressed_size_saturated: bool,
+        header_offset_saturated: bool,
+        disk_start_saturated: bool,
+    ) -> Option<Self> {
+        let mut reader = Reader { data };
+        let mut read_u64 = |saturated: bool| {
+            if saturated {
+                reader.u64().map(Some)
+            } else {
+                Some(None)
+            }
+        };
+        let size = read_u64(size_saturated)?;
+        let compressed_size = read_u64(compressed_size_saturated)?;
+        let header_offset = read_u64(header_offset_saturated)?;
+        let disk_start = if disk_start_saturated {
+            Some(reader.u32()?)
+        } else {
+            None
+        };
+        if !reader.is_empty() {
+            return None;
+        }
+        Some(Zip64 {
+            size,
+            compressed_size,
+            header_offset,
+            disk_start,
+        })
+    }
+
+    /// Serialize the extra field into its data, without the id and length header.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for value in [self.size, self.compressed_size, self.header_offset]
+            .iter()
+            .flatten()
+        {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        if let Some(disk_start) = self.disk_start {
+            data.extend_from_slice(&disk_start.to_le_bytes());
+        }
+        data
+    }
+}
+
+/// The Info-ZIP Unicode path extra field (0x7075).
+/// The CRC is that of the name stored in the header, and lets readers detect when the header
+/// name was changed by a tool that did not update this field.
+/// libzip manages this field itself, so it does not implement [ExtraField].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UnicodePath {
+    pub name_crc: u32,
+    pub name: String,
+}
+
+impl UnicodePath {
+    /// The header id of the extra field.
+    pub const ID: u16 = 0x7075;
+
+    /// Parse the data of the extra field, returning `None` if it is malformed.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader { data };
+        if reader.u8()? != 1 {
+            return None;
+        }
+        let name_crc = reader.u32()?;
+        let name = String::from_utf8(reader.data.to_vec()).ok()?;
+        Some(UnicodePath { name_crc, name })
+    }
+
+    /// Serialize the extra field into its data, without the id and length header.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = vec![1];
+        data.extend_from_slice(&self.name_crc.to_le_bytes());
+        data.extend_from_slice(self.name.as_bytes());
+        data
+    }
+}
+
+/// The WinZip AES encryption extra field (0x9901).
+/// libzip manages this field itself, so it does not implement [ExtraField].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WinZipAes {
+    /// The vendor version: 1 for AE-1, which stores a CRC, or 2 for AE-2, which does not.
+    pub version: u16,
+    /// The key strength: 1 for 128, 2 for 192 or 3 for 256 bit keys.
+    pub strength: u8,
+    /// The raw `ZIP_CM_*` compression method of the encrypted data.
+    pub compression_method: u16,
+}
+
+impl WinZipAes {
+    /// The header id of the extra field.
+    pub const ID: u16 = 0x9901;
+
+    const VENDOR_ID: &'static [u8] = b"AE";
+
+    /// Parse the data of the extra field, returning `None` if it is malformed.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader { data };
+        let version = reader.u16()?;
+        if reader.bytes(2)? != WinZipAes::VENDOR_ID {
+            return None;
+        }
+        let strength = reader.u8()?;
+        let compression_method = reader.u16()?;
+        Some(WinZipAes {
+            version,
+            strength,
+            compression_method,
+        })
+    }
+
+    /// Serialize the extra field into its data, without the id and length header.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(7);
+        data.extend_from_slice(&self.version.to_le_bytes());
+        data.extend_from_slice(WinZipAes::VENDOR_ID);
+        data.push(self.strength);
+        data.extend_from_slice(&self.compression_method.to_le_bytes());
+        data
+    }
+}
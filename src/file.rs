@@ -3,9 +3,11 @@ use crate::error::ZipErrorT;
 use crate::ffi;
 use crate::Error;
 use crate::Result;
+use std::ffi::{CStr, CString};
 use std::io;
 use std::marker::PhantomData;
 use std::ptr::null_mut;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Encoding {
@@ -23,6 +25,319 @@ pub enum Encryption {
     PkWare,
 }
 
+impl Encryption {
+    pub(crate) fn value(self) -> u16 {
+        (match self {
+            Encryption::None => ffi::ZIP_EM_NONE,
+            Encryption::AES128 => ffi::ZIP_EM_AES_128,
+            Encryption::AES192 => ffi::ZIP_EM_AES_192,
+            Encryption::AES256 => ffi::ZIP_EM_AES_256,
+            Encryption::PkWare => ffi::ZIP_EM_TRAD_PKWARE,
+        }) as _
+    }
+
+    /// Whether the linked libzip can encrypt (or, if `encode` is false, decrypt) with this
+    /// method.
+    pub fn is_supported(self, encode: bool) -> bool {
+        unsafe { ffi::zip_encryption_method_supported(self.value(), encode as _) != 0 }
+    }
+}
+
+// The PKWARE strong encryption methods are disabled in zip.h, so the bindings lack them.
+const ZIP_EM_DES: u32 = 0x6601;
+const ZIP_EM_RC2_OLD: u32 = 0x6602;
+const ZIP_EM_3DES_168: u32 = 0x6603;
+const ZIP_EM_3DES_112: u32 = 0x6609;
+const ZIP_EM_PKZIP_AES_128: u32 = 0x660e;
+const ZIP_EM_PKZIP_AES_192: u32 = 0x660f;
+const ZIP_EM_PKZIP_AES_256: u32 = 0x6610;
+const ZIP_EM_RC2: u32 = 0x6702;
+const ZIP_EM_RC4: u32 = 0x6801;
+
+/// The encryption method used by an existing entry, as reported by [Archive::stat].
+/// libzip does not parse the strong encryption header yet, so it currently reports entries using
+/// the PKWARE strong encryption methods as `Unknown`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EncryptionMethod {
+    None,
+    PkWare,
+    Des,
+    Rc2Old,
+    TripleDes168,
+    TripleDes112,
+    PkZipAES128,
+    PkZipAES192,
+    PkZipAES256,
+    Rc2,
+    Rc4,
+    AES128,
+    AES192,
+    AES256,
+    Unknown,
+}
+
+impl EncryptionMethod {
+    pub(crate) fn value(self) -> u16 {
+        (match self {
+            EncryptionMethod::None => ffi::ZIP_EM_NONE,
+            EncryptionMethod::PkWare => ffi::ZIP_EM_TRAD_PKWARE,
+            EncryptionMethod::Des => ZIP_EM_DES,
+            EncryptionMethod::Rc2Old => ZIP_EM_RC2_OLD,
+            EncryptionMethod::TripleDes168 => ZIP_EM_3DES_168,
+            EncryptionMethod::TripleDes112 => ZIP_EM_3DES_112,
+            EncryptionMethod::PkZipAES128 => ZIP_EM_PKZIP_AES_128,
+            EncryptionMethod::PkZipAES192 => ZIP_EM_PKZIP_AES_192,
+            EncryptionMethod::PkZipAES256 => ZIP_EM_PKZIP_AES_256,
+            EncryptionMethod::Rc2 => ZIP_EM_RC2,
+            EncryptionMethod::Rc4 => ZIP_EM_RC4,
+            EncryptionMethod::AES128 => ffi::ZIP_EM_AES_128,
+            EncryptionMethod::AES192 => ffi::ZIP_EM_AES_192,
+            EncryptionMethod::AES256 => ffi::ZIP_EM_AES_256,
+            EncryptionMethod::Unknown => ffi::ZIP_EM_UNKNOWN,
+        }) as _
+    }
+}
+
+impl From<u16> for EncryptionMethod {
+    fn from(method: u16) -> Self {
+        match method as _ {
+            ffi::ZIP_EM_NONE => EncryptionMethod::None,
+            ffi::ZIP_EM_TRAD_PKWARE => EncryptionMethod::PkWare,
+            ZIP_EM_DES => EncryptionMethod::Des,
+            ZIP_EM_RC2_OLD => EncryptionMethod::Rc2Old,
+            ZIP_EM_3DES_168 => EncryptionMethod::TripleDes168,
+            ZIP_EM_3DES_112 => EncryptionMethod::TripleDes112,
+            ZIP_EM_PKZIP_AES_128 => EncryptionMethod::PkZipAES128,
+            ZIP_EM_PKZIP_AES_192 => EncryptionMethod::PkZipAES192,
+            ZIP_EM_PKZIP_AES_256 => EncryptionMethod::PkZipAES256,
+            ZIP_EM_RC2 => EncryptionMethod::Rc2,
+            ZIP_EM_RC4 => EncryptionMethod::Rc4,
+            ffi::ZIP_EM_AES_128 => EncryptionMethod::AES128,
+            ffi::ZIP_EM_AES_192 => EncryptionMethod::AES192,
+            ffi::ZIP_EM_AES_256 => EncryptionMethod::AES256,
+            _ => EncryptionMethod::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompressionMethod {
+    /// Let libzip choose between Deflate and Store.
+    Default,
+    Store,
+    Deflate,
+    Bzip2,
+    Lzma,
+    Xz,
+    Zstd,
+    /// Any other `ZIP_CM_*` method, as found in existing archives.
+    Unknown(u16),
+}
+
+impl CompressionMethod {
+    pub(crate) fn value(self) -> i32 {
+        match self {
+            CompressionMethod::Default => ffi::ZIP_CM_DEFAULT,
+            CompressionMethod::Store => ffi::ZIP_CM_STORE as _,
+            CompressionMethod::Deflate => ffi::ZIP_CM_DEFLATE as _,
+            CompressionMethod::Bzip2 => ffi::ZIP_CM_BZIP2 as _,
+            CompressionMethod::Lzma => ffi::ZIP_CM_LZMA as _,
+            CompressionMethod::Xz => ffi::ZIP_CM_XZ as _,
+            CompressionMethod::Zstd => ffi::ZIP_CM_ZSTD as _,
+            CompressionMethod::Unknown(method) => method as _,
+        }
+    }
+
+    /// Whether the linked libzip can compress (or, if `compress` is false, decompress) with this
+    /// method.
+    pub fn is_supported(self, compress: bool) -> bool {
+        unsafe { ffi::zip_compression_method_supported(self.value(), compress as _) != 0 }
+    }
+}
+
+impl From<u16> for CompressionMethod {
+    fn from(method: u16) -> Self {
+        match method as _ {
+            ffi::ZIP_CM_STORE => CompressionMethod::Store,
+            ffi::ZIP_CM_DEFLATE => CompressionMethod::Deflate,
+            ffi::ZIP_CM_BZIP2 => CompressionMethod::Bzip2,
+            ffi::ZIP_CM_LZMA => CompressionMethod::Lzma,
+            ffi::ZIP_CM_XZ => CompressionMethod::Xz,
+            ffi::ZIP_CM_ZSTD => CompressionMethod::Zstd,
+            _ => CompressionMethod::Unknown(method),
+        }
+    }
+}
+
+/// The host system that created an entry, which determines how its external attributes are
+/// interpreted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OperatingSystem {
+    Dos,
+    Amiga,
+    OpenVms,
+    Unix,
+    VmCms,
+    AtariSt,
+    Os2,
+    Macintosh,
+    ZSystem,
+    Cpm,
+    WindowsNtfs,
+    Mvs,
+    Vse,
+    AcornRisc,
+    Vfat,
+    AlternateMvs,
+    BeOs,
+    Tandem,
+    Os400,
+    OsX,
+    Unknown(u8),
+}
+
+impl OperatingSystem {
+    pub(crate) fn value(self) -> u8 {
+        (match self {
+            OperatingSystem::Dos => ffi::ZIP_OPSYS_DOS,
+            OperatingSystem::Amiga => ffi::ZIP_OPSYS_AMIGA,
+            OperatingSystem::OpenVms => ffi::ZIP_OPSYS_OPENVMS,
+            OperatingSystem::Unix => ffi::ZIP_OPSYS_UNIX,
+            OperatingSystem::VmCms => ffi::ZIP_OPSYS_VM_CMS,
+            OperatingSystem::AtariSt => ffi::ZIP_OPSYS_ATARI_ST,
+            OperatingSystem::Os2 => ffi::ZIP_OPSYS_OS_2,
+            OperatingSystem::Macintosh => ffi::ZIP_OPSYS_MACINTOSH,
+            OperatingSystem::ZSystem => ffi::ZIP_OPSYS_Z_SYSTEM,
+            OperatingSystem::Cpm => ffi::ZIP_OPSYS_CPM,
+            OperatingSystem::WindowsNtfs => ffi::ZIP_OPSYS_WINDOWS_NTFS,
+            OperatingSystem::Mvs => ffi::ZIP_OPSYS_MVS,
+            OperatingSystem::Vse => ffi::ZIP_OPSYS_VSE,
+            OperatingSystem::AcornRisc => ffi::ZIP_OPSYS_ACORN_RISC,
+            OperatingSystem::Vfat => ffi::ZIP_OPSYS_VFAT,
+            OperatingSystem::AlternateMvs => ffi::ZIP_OPSYS_ALTERNATE_MVS,
+            OperatingSystem::BeOs => ffi::ZIP_OPSYS_BEOS,
+            OperatingSystem::Tandem => ffi::ZIP_OPSYS_TANDEM,
+            OperatingSystem::Os400 => ffi::ZIP_OPSYS_OS_400,
+            OperatingSystem::OsX => ffi::ZIP_OPSYS_OS_X,
+            OperatingSystem::Unknown(opsys) => return opsys,
+        }) as _
+    }
+}
+
+impl From<u8> for OperatingSystem {
+    fn from(opsys: u8) -> Self {
+        match opsys as _ {
+            ffi::ZIP_OPSYS_DOS => OperatingSystem::Dos,
+            ffi::ZIP_OPSYS_AMIGA => OperatingSystem::Amiga,
+            ffi::ZIP_OPSYS_OPENVMS => OperatingSystem::OpenVms,
+            ffi::ZIP_OPSYS_UNIX => OperatingSystem::Unix,
+            ffi::ZIP_OPSYS_VM_CMS => OperatingSystem::VmCms,
+            ffi::ZIP_OPSYS_ATARI_ST => OperatingSystem::AtariSt,
+            ffi::ZIP_OPSYS_OS_2 => OperatingSystem::Os2,
+            ffi::ZIP_OPSYS_MACINTOSH => OperatingSystem::Macintosh,
+            ffi::ZIP_OPSYS_Z_SYSTEM => OperatingSystem::ZSystem,
+            ffi::ZIP_OPSYS_CPM => OperatingSystem::Cpm,
+            ffi::ZIP_OPSYS_WINDOWS_NTFS => OperatingSystem::WindowsNtfs,
+            ffi::ZIP_OPSYS_MVS => OperatingSystem::Mvs,
+            ffi::ZIP_OPSYS_VSE => OperatingSystem::Vse,
+            ffi::ZIP_OPSYS_ACORN_RISC => OperatingSystem::AcornRisc,
+            ffi::ZIP_OPSYS_VFAT => OperatingSystem::Vfat,
+            ffi::ZIP_OPSYS_ALTERNATE_MVS => OperatingSystem::AlternateMvs,
+            ffi::ZIP_OPSYS_BEOS => OperatingSystem::BeOs,
+            ffi::ZIP_OPSYS_TANDEM => OperatingSystem::Tandem,
+            ffi::ZIP_OPSYS_OS_400 => OperatingSystem::Os400,
+            ffi::ZIP_OPSYS_OS_X => OperatingSystem::OsX,
+            _ => OperatingSystem::Unknown(opsys),
+        }
+    }
+}
+
+const S_IFMT: u16 = 0o170000;
+const S_IFREG: u16 = 0o100000;
+const S_IFLNK: u16 = 0o120000;
+const S_IXALL: u16 = 0o111;
+/// The mode of a regular file readable by everyone, for attributes that lack a Unix mode.
+const DEFAULT_FILE_MODE: u16 = 0o100644;
+
+/// The external attributes of an entry.
+/// Their meaning depends on the operating system; for Unix the upper 16 bits hold the file mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExternalAttributes {
+    pub opsys: OperatingSystem,
+    pub attributes: u32,
+}
+
+impl ExternalAttributes {
+    /// Unix attributes with the given file mode, such as `0o100755` for an executable file.
+    pub fn unix(mode: u16) -> Self {
+        ExternalAttributes {
+            opsys: OperatingSystem::Unix,
+            attributes: (mode as u32) << 16,
+        }
+    }
+
+    /// The Unix file mode, if these are Unix attributes.
+    pub fn unix_mode(&self) -> Option<u16> {
+        if self.opsys == OperatingSystem::Unix {
+            Some((self.attributes >> 16) as _)
+        } else {
+            None
+        }
+    }
+
+    /// Set the Unix file mode, switching to Unix attributes if necessary.
+    /// The lower 16 bits, used for MS-DOS attributes, are kept.
+    pub fn set_unix_mode(&mut self, mode: u16) {
+        self.opsys = OperatingSystem::Unix;
+        self.attributes = ((mode as u32) << 16) | (self.attributes & 0xffff);
+    }
+
+    /// Whether these are Unix attributes of a symbolic link.
+    pub fn is_symlink(&self) -> bool {
+        self.unix_mode()
+            .is_some_and(|mode| mode & S_IFMT == S_IFLNK)
+    }
+
+    /// Whether these are Unix attributes with any of the executable bits set.
+    pub fn is_executable(&self) -> bool {
+        self.unix_mode().is_some_and(|mode| mode & S_IXALL != 0)
+    }
+
+    /// Set or clear all of the Unix executable bits, switching to Unix attributes if necessary.
+    /// A Unix mode without a file type is treated as a regular file with the same permissions,
+    /// and attributes without any Unix mode as a regular file with mode `0o644`.
+    pub fn set_executable(&mut self, executable: bool) {
+        let mode = match self.unix_mode() {
+            Some(0) | None => DEFAULT_FILE_MODE,
+            Some(mode) if mode & S_IFMT == 0 => mode | S_IFREG,
+            Some(mode) => mode,
+        };
+        if executable {
+            self.set_unix_mode(mode | S_IXALL);
+        } else {
+            self.set_unix_mode(mode & !S_IXALL);
+        }
+    }
+}
+
+/// Which header of an entry an extra field is stored in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Location {
+    Local,
+    Central,
+    Both,
+}
+
+impl Location {
+    pub(crate) fn value(self) -> ffi::zip_flags_t {
+        match self {
+            Location::Local => ffi::ZIP_FL_LOCAL,
+            Location::Central => ffi::ZIP_FL_CENTRAL,
+            Location::Both => ffi::ZIP_FL_LOCAL | ffi::ZIP_FL_CENTRAL,
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub enum OpenFlag {
     Compressed,
@@ -38,10 +353,127 @@ pub enum LocateFlag {
     EncodingStrict,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NameFlag {
+    Unchanged,
+    EncodingRaw,
+    EncodingGuess,
+    EncodingStrict,
+}
+
+/// An entry in an archive, as yielded by [Archive::entries].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Entry<'a> {
+    pub(crate) index: u64,
+    pub(crate) name: &'a CStr,
+}
+
+impl<'a> Entry<'a> {
+    /// The index of the entry within the archive.
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// The name of the entry, as stored in (or decoded from) the archive.
+    pub fn name(&self) -> &'a CStr {
+        self.name
+    }
+
+    /// Whether the entry is a directory, which zip marks with a trailing slash on the name.
+    pub fn is_dir(&self) -> bool {
+        self.name.to_bytes().ends_with(b"/")
+    }
+}
+
+/// Metadata about an entry in an archive, as returned by [Archive::stat] and
+/// [Archive::stat_index].
+/// Any information that libzip could not provide is `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Stat {
+    pub name: Option<CString>,
+    pub index: Option<u64>,
+    /// The uncompressed size of the entry.
+    pub size: Option<u64>,
+    pub compressed_size: Option<u64>,
+    /// The modification time, in seconds since the Unix epoch.
+    pub mtime: Option<i64>,
+    pub crc: Option<u32>,
+    pub compression_method: Option<CompressionMethod>,
+    pub encryption_method: Option<EncryptionMethod>,
+}
+
+impl Stat {
+    /// The modification time as a [SystemTime].
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.mtime.map(|mtime| {
+            if mtime >= 0 {
+                UNIX_EPOCH + Duration::from_secs(mtime as _)
+            } else {
+                UNIX_EPOCH - Duration::from_secs(mtime.unsigned_abs())
+            }
+        })
+    }
+
+    /// Copy the valid fields out of a zip_stat_t.
+    /// The name pointer, if marked valid, must point to a valid C string.
+    pub(crate) unsafe fn from_raw(stat: &ffi::zip_stat_t) -> Stat {
+        let valid = |flag: u32| stat.valid & flag as u64 != 0;
+        Stat {
+            name: if valid(ffi::ZIP_STAT_NAME) && !stat.name.is_null() {
+                Some(CStr::from_ptr(stat.name).to_owned())
+            } else {
+                None
+            },
+            index: Some(stat.index).filter(|_| valid(ffi::ZIP_STAT_INDEX)),
+            size: Some(stat.size).filter(|_| valid(ffi::ZIP_STAT_SIZE)),
+            compressed_size: Some(stat.comp_size).filter(|_| valid(ffi::ZIP_STAT_COMP_SIZE)),
+            mtime: Some(stat.mtime as _).filter(|_| valid(ffi::ZIP_STAT_MTIME)),
+            crc: Some(stat.crc).filter(|_| valid(ffi::ZIP_STAT_CRC)),
+            compression_method: Some(stat.comp_method.into())
+                .filter(|_| valid(ffi::ZIP_STAT_COMP_METHOD)),
+            encryption_method: Some(stat.encryption_method.into())
+                .filter(|_| valid(ffi::ZIP_STAT_ENCRYPTION_METHOD)),
+        }
+    }
+
+    /// Copy the fields that are present into a zip_stat_t, marking them valid.
+    /// The name is not copied, as the zip_stat_t could outlive it.
+    pub(crate) fn to_raw(&self, stat: &mut ffi::zip_stat_t) {
+        if let Some(index) = self.index {
+            stat.index = index;
+            stat.valid |= ffi::ZIP_STAT_INDEX as u64;
+        }
+        if let Some(size) = self.size {
+            stat.size = size;
+            stat.valid |= ffi::ZIP_STAT_SIZE as u64;
+        }
+        if let Some(compressed_size) = self.compressed_size {
+            stat.comp_size = compressed_size;
+            stat.valid |= ffi::ZIP_STAT_COMP_SIZE as u64;
+        }
+        if let Some(mtime) = self.mtime {
+            stat.mtime = mtime as _;
+            stat.valid |= ffi::ZIP_STAT_MTIME as u64;
+        }
+        if let Some(crc) = self.crc {
+            stat.crc = crc;
+            stat.valid |= ffi::ZIP_STAT_CRC as u64;
+        }
+        if let Some(compression_method) = self.compression_method {
+            stat.comp_method = compression_method.value() as _;
+            stat.valid |= ffi::ZIP_STAT_COMP_METHOD as u64;
+        }
+        if let Some(encryption_method) = self.encryption_method {
+            stat.encryption_method = encryption_method.value();
+            stat.valid |= ffi::ZIP_STAT_ENCRYPTION_METHOD as u64;
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct File<'a> {
     pub(crate) handle: *mut ffi::zip_file_t,
-    pub(crate) phantom: PhantomData<&'a Archive>,
+    pub(crate) phantom: PhantomData<&'a Archive<'a>>,
 }
 
 impl File<'_> {
@@ -82,7 +514,7 @@ impl io::Read for File<'_> {
         let result = unsafe { ffi::zip_fread(self.handle, buf.as_mut_ptr() as _, buf.len() as _) };
         if result == -1 {
             let error: Error = self.error().into();
-            Err(io::Error::new(io::ErrorKind::Other, error))
+            Err(io::Error::other(error))
         } else {
             Ok(result as _)
         }
@@ -104,7 +536,7 @@ impl io::Seek for File<'_> {
         };
         if result == -1 {
             let error: Error = self.error().into();
-            Err(io::Error::new(io::ErrorKind::Other, error))
+            Err(io::Error::other(error))
         } else {
             unsafe {
                 // Assume this will work, otherwise the fseek would have already failed.
@@ -1,5 +1,6 @@
 pub mod archive;
 pub mod error;
+pub mod extra_field;
 pub mod file;
 pub mod source;
 
@@ -10,15 +11,60 @@ use is_libzip_sys as ffi;
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::cell::RefCell;
     use std::convert::TryInto;
     use std::ffi::{CStr, CString};
     use std::io::Read;
+    use std::rc::Rc;
 
     use std::string::String;
+    use std::time::{Duration, UNIX_EPOCH};
 
     use tempdir::TempDir;
 
+    /// Create an archive at `zip_path` holding the given entries, letting `setup` change it
+    /// before it is closed.
+    fn create_archive<F>(zip_path: &CStr, entries: &[(&str, &str)], setup: F)
+    where
+        F: FnOnce(&mut archive::Archive),
+    {
+        let file_source: source::Source<source::File> = zip_path.try_into().unwrap();
+        let mut archive = archive::Archive::open(
+            file_source,
+            [archive::OpenFlag::Create, archive::OpenFlag::Exclusive],
+        )
+        .unwrap();
+        for (name, content) in entries {
+            let content_source: source::Source<&[u8]> = content.as_bytes().try_into().unwrap();
+            archive
+                .add(
+                    CString::new(*name).unwrap(),
+                    content_source,
+                    file::Encoding::Guess,
+                    false,
+                )
+                .unwrap();
+        }
+        setup(&mut archive);
+        archive.close().unwrap();
+    }
+
+    /// Open the archive at `zip_path` read-only.
+    fn open_read_only(zip_path: &CStr) -> archive::Archive<'static> {
+        let file_source: source::Source<source::File> = zip_path.try_into().unwrap();
+        archive::Archive::open(
+            file_source,
+            [
+                archive::OpenFlag::CheckConsistency,
+                archive::OpenFlag::ReadOnly,
+            ],
+        )
+        .unwrap()
+    }
+
     #[test]
+    // Kept as originally written, passing names and flags by reference.
+    #[allow(clippy::needless_borrows_for_generic_args)]
     fn round_trip() {
         let tempdir = TempDir::new("test").unwrap();
         let zip_path = CString::new(tempdir.path().join("file.zip").to_str().unwrap()).unwrap();
@@ -82,4 +128,1217 @@ mod tests {
             archive.close().unwrap();
         }
     }
+
+    #[test]
+    fn entries() {
+        let tempdir = TempDir::new("test").unwrap();
+        let zip_path = CString::new(tempdir.path().join("file.zip").to_str().unwrap()).unwrap();
+        create_archive(&zip_path, &[("foo", "foo"), ("bar", "bar")], |_| ());
+
+        let archive = open_read_only(&zip_path);
+        assert_eq!(archive.len(), 2);
+        assert!(!archive.is_empty());
+        assert_eq!(archive.name(1, []).unwrap().to_bytes(), b"bar");
+        let names: Vec<_> = archive
+            .entries([])
+            .map(|entry| {
+                let entry = entry.unwrap();
+                (entry.index(), entry.name().to_owned())
+            })
+            .collect();
+        assert_eq!(
+            names,
+            [
+                (0, CString::new("foo").unwrap()),
+                (1, CString::new("bar").unwrap())
+            ]
+        );
+        archive.close().unwrap();
+    }
+
+    #[test]
+    fn stat() {
+        let tempdir = TempDir::new("test").unwrap();
+        let zip_path = CString::new(tempdir.path().join("file.zip").to_str().unwrap()).unwrap();
+        let foo = "Lorem ipsum dolor sit amet";
+        create_archive(&zip_path, &[("foo", foo)], |_| ());
+
+        let archive = open_read_only(&zip_path);
+        let foo_stat = archive.stat(CString::new("foo").unwrap(), [], []).unwrap();
+        assert_eq!(foo_stat.name, Some(CString::new("foo").unwrap()));
+        assert_eq!(foo_stat.index, Some(0));
+        assert_eq!(foo_stat.size, Some(foo.len() as u64));
+        assert!(foo_stat.compressed_size.is_some());
+        assert!(foo_stat.crc.is_some());
+        assert_eq!(archive.stat_index(0, []).unwrap(), foo_stat);
+        assert!(archive.stat_index(1, []).is_err());
+        archive.close().unwrap();
+    }
+
+    #[test]
+    fn locate() {
+        let tempdir = TempDir::new("test").unwrap();
+        let zip_path = CString::new(tempdir.path().join("file.zip").to_str().unwrap()).unwrap();
+        create_archive(
+            &zip_path,
+            &[
+                ("foo", "foo"),
+                ("META-INF/MANIFEST.MF", "Manifest-Version: 1.0"),
+            ],
+            |_| (),
+        );
+
+        let archive = open_read_only(&zip_path);
+        assert_eq!(
+            archive.locate(CString::new("foo").unwrap(), []).unwrap(),
+            Some(0)
+        );
+        assert_eq!(
+            archive
+                .locate(
+                    CString::new("meta-inf/manifest.mf").unwrap(),
+                    [file::LocateFlag::NoCase]
+                )
+                .unwrap(),
+            Some(1)
+        );
+        assert_eq!(
+            archive
+                .locate(CString::new("meta-inf/manifest.mf").unwrap(), [])
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            archive.locate(CString::new("baz").unwrap(), []).unwrap(),
+            None
+        );
+        archive.close().unwrap();
+    }
+
+    #[test]
+    fn compression() {
+        let tempdir = TempDir::new("test").unwrap();
+        let zip_path = CString::new(tempdir.path().join("file.zip").to_str().unwrap()).unwrap();
+        let content = "Lorem ipsum ".repeat(100);
+        assert!(file::CompressionMethod::Store.is_supported(true));
+        assert!(file::CompressionMethod::Deflate.is_supported(true));
+        create_archive(
+            &zip_path,
+            &[("stored", &content), ("deflated", &content)],
+            |archive| {
+                archive
+                    .set_compression(0, file::CompressionMethod::Store, 0)
+                    .unwrap();
+                archive
+                    .set_compression(1, file::CompressionMethod::Deflate, 9)
+                    .unwrap();
+            },
+        );
+
+        let mut archive = open_read_only(&zip_path);
+        let stored = archive.stat_index(0, []).unwrap();
+        assert_eq!(
+            stored.compression_method,
+            Some(file::CompressionMethod::Store)
+        );
+        assert_eq!(stored.compressed_size, stored.size);
+        let deflated = archive.stat_index(1, []).unwrap();
+        assert_eq!(
+            deflated.compression_method,
+            Some(file::CompressionMethod::Deflate)
+        );
+        assert!(deflated.compressed_size < deflated.size);
+        let mut deflated_buf = String::new();
+        archive
+            .open_index(1, [])
+            .unwrap()
+            .read_to_string(&mut deflated_buf)
+            .unwrap();
+        assert_eq!(deflated_buf, content);
+        archive.close().unwrap();
+    }
+
+    #[test]
+    fn encryption_method() {
+        let tempdir = TempDir::new("test").unwrap();
+        let zip_path = CString::new(tempdir.path().join("file.zip").to_str().unwrap()).unwrap();
+        let foo = "Lorem ipsum dolor sit amet";
+        let pkware = file::Encryption::PkWare.is_supported(true);
+        assert!(file::Encryption::None.is_supported(true));
+        create_archive(&zip_path, &[("plain", foo), ("secret", foo)], |archive| {
+            if pkware {
+                archive
+                    .set_encryption_with_password(
+                        file::Encryption::PkWare,
+                        1,
+                        CString::new("hunter2").unwrap(),
+                    )
+                    .unwrap();
+            }
+        });
+
+        let archive = open_read_only(&zip_path);
+        assert_eq!(
+            archive.stat_index(0, []).unwrap().encryption_method,
+            Some(file::EncryptionMethod::None)
+        );
+        if pkware {
+            assert_eq!(
+                archive.stat_index(1, []).unwrap().encryption_method,
+                Some(file::EncryptionMethod::PkWare)
+            );
+        } else {
+            eprintln!("skipping the PKWARE checks, as libzip was built without them");
+        }
+        archive.close().unwrap();
+
+        assert_eq!(
+            file::EncryptionMethod::from(0x6601),
+            file::EncryptionMethod::Des
+        );
+        assert_eq!(
+            file::EncryptionMethod::from(0x6801),
+            file::EncryptionMethod::Rc4
+        );
+        assert_eq!(
+            file::EncryptionMethod::from(0xfffe),
+            file::EncryptionMethod::Unknown
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let tempdir = TempDir::new("test").unwrap();
+        let zip_path = CString::new(tempdir.path().join("file.zip").to_str().unwrap()).unwrap();
+        create_archive(&zip_path, &[("foo", "foo"), ("bar", "bar")], |archive| {
+            archive.set_comment("built from abc123").unwrap();
+            archive
+                .set_entry_comment(0, "f\u{f6}\u{f6} comment", file::Encoding::Utf8)
+                .unwrap();
+            assert!(archive.set_comment(vec![b'x'; 1 << 16]).is_err());
+        });
+
+        let archive = open_read_only(&zip_path);
+        assert_eq!(archive.comment([]).unwrap(), b"built from abc123");
+        assert_eq!(
+            archive.comment_str(file::Encoding::Guess).unwrap(),
+            "built from abc123"
+        );
+        assert_eq!(
+            archive.entry_comment_str(0, file::Encoding::Guess).unwrap(),
+            "f\u{f6}\u{f6} comment"
+        );
+        assert_eq!(archive.entry_comment(1, []).unwrap(), b"");
+        archive.close().unwrap();
+    }
+
+    #[test]
+    fn mtime() {
+        let tempdir = TempDir::new("test").unwrap();
+        let zip_path = CString::new(tempdir.path().join("file.zip").to_str().unwrap()).unwrap();
+        // DOS times have a resolution of two seconds, so pick an even time.
+        let pinned = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        create_archive(&zip_path, &[("foo", "foo"), ("bar", "bar")], |archive| {
+            archive.set_mtime(0, pinned).unwrap();
+            // 1980-01-01 00:00:00, the DOS epoch.
+            archive.set_dos_time(1, 0, (1 << 5) | 1).unwrap();
+        });
+
+        let archive = open_read_only(&zip_path);
+        let foo_stat = archive.stat_index(0, []).unwrap();
+        assert_eq!(foo_stat.modified(), Some(pinned));
+        assert_eq!(foo_stat.mtime, Some(1_000_000_000));
+        // The DOS time is in local time, so only check that it is around 1980.
+        let bar_modified = archive.stat_index(1, []).unwrap().modified().unwrap();
+        let dos_epoch = UNIX_EPOCH + Duration::from_secs(315_532_800);
+        let day = Duration::from_secs(24 * 60 * 60);
+        assert!(bar_modified > dos_epoch - day && bar_modified < dos_epoch + day);
+        archive.close().unwrap();
+    }
+
+    #[test]
+    fn external_attributes() {
+        let mut script = file::ExternalAttributes::unix(0o100644);
+        assert!(!script.is_executable());
+        script.set_executable(true);
+        assert_eq!(script.unix_mode(), Some(0o100755));
+        script.set_executable(false);
+        assert_eq!(script.unix_mode(), Some(0o100644));
+        script.set_executable(true);
+        let mut dos = file::ExternalAttributes {
+            opsys: file::OperatingSystem::Dos,
+            attributes: 0x20,
+        };
+        assert_eq!(dos.unix_mode(), None);
+        dos.set_executable(true);
+        assert_eq!(dos.opsys, file::OperatingSystem::Unix);
+        assert_eq!(dos.unix_mode(), Some(0o100755));
+        assert_eq!(dos.attributes & 0xffff, 0x20);
+        dos.set_unix_mode(0o100600);
+        assert_eq!(dos.attributes, 0o100600 << 16 | 0x20);
+        let mut modeless = file::ExternalAttributes::unix(0);
+        modeless.set_executable(true);
+        assert_eq!(modeless.unix_mode(), Some(0o100755));
+        let mut private = file::ExternalAttributes::unix(0o600);
+        private.set_executable(true);
+        assert_eq!(private.unix_mode(), Some(0o100711));
+
+        let tempdir = TempDir::new("test").unwrap();
+        let zip_path = CString::new(tempdir.path().join("file.zip").to_str().unwrap()).unwrap();
+        create_archive(
+            &zip_path,
+            &[("run.sh", "#!/bin/sh"), ("link", "run.sh")],
+            |archive| {
+                archive.set_external_attributes(0, script).unwrap();
+                archive
+                    .set_external_attributes(1, file::ExternalAttributes::unix(0o120777))
+                    .unwrap();
+            },
+        );
+
+        let archive = open_read_only(&zip_path);
+        let script = archive.external_attributes(0, []).unwrap();
+        assert_eq!(script.unix_mode(), Some(0o100755));
+        assert!(script.is_executable());
+        assert!(!script.is_symlink());
+        let link = archive.external_attributes(1, []).unwrap();
+        assert_eq!(link.unix_mode(), Some(0o120777));
+        assert!(link.is_symlink());
+        archive.close().unwrap();
+    }
+
+    #[test]
+    fn extra_fields() {
+        use file::Location::{Both, Central, Local};
+
+        let tempdir = TempDir::new("test").unwrap();
+        let zip_path = CString::new(tempdir.path().join("file.zip").to_str().unwrap()).unwrap();
+        create_archive(&zip_path, &[("foo", "foo"), ("bar", "bar")], |archive| {
+            archive
+                .add_extra_field(0, 0xcafe, b"vendor", Central)
+                .unwrap();
+            archive
+                .add_extra_field(0, 0xcafe, b"second", Central)
+                .unwrap();
+            archive
+                .set_extra_field(0, 0xcafe, 1, b"replaced", Central)
+                .unwrap();
+            archive.add_extra_field(0, 0xbeef, b"local", Local).unwrap();
+            archive.add_extra_field(1, 0xdead, b"gone", Both).unwrap();
+            archive.add_extra_field(1, 0xdead, b"gone", Both).unwrap();
+            archive
+                .delete_extra_field_by_id(1, 0xdead, None, Both)
+                .unwrap();
+            assert!(archive
+                .add_extra_field(1, 0xcafe, vec![0; 1 << 16], Central)
+                .is_err());
+        });
+
+        let archive = open_read_only(&zip_path);
+        assert_eq!(
+            archive
+                .extra_fields_count_by_id(0, 0xcafe, Central)
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            archive.extra_field_by_id(0, 0xcafe, 1, Central).unwrap(),
+            b"replaced"
+        );
+        let central: Vec<_> = archive
+            .extra_fields(0, Central)
+            .unwrap()
+            .map(|field| field.unwrap())
+            .filter(|(id, _)| *id == 0xcafe)
+            .collect();
+        assert_eq!(
+            central,
+            [(0xcafe, &b"vendor"[..]), (0xcafe, &b"replaced"[..])]
+        );
+        assert_eq!(
+            archive.extra_field_by_id(0, 0xbeef, 0, Local).unwrap(),
+            b"local"
+        );
+        assert_eq!(
+            archive
+                .extra_fields_count_by_id(0, 0xbeef, Central)
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            archive.extra_fields_count_by_id(1, 0xdead, Both).unwrap(),
+            0
+        );
+        archive.close().unwrap();
+    }
+
+    #[test]
+    fn read_only() {
+        let tempdir = TempDir::new("test").unwrap();
+        let zip_path = CString::new(tempdir.path().join("file.zip").to_str().unwrap()).unwrap();
+        create_archive(&zip_path, &[("foo", "foo")], |_| ());
+
+        let archive = open_read_only(&zip_path);
+        assert!(archive.is_read_only().unwrap());
+        assert!(archive.opened_read_only());
+        archive.close().unwrap();
+
+        let file_source: source::Source<source::File> = (&zip_path as &CStr).try_into().unwrap();
+        let mut archive = archive::Archive::open(file_source, []).unwrap();
+        assert!(!archive.is_read_only().unwrap());
+        assert!(!archive.opened_read_only());
+        archive.set_read_only(true).unwrap();
+        assert!(archive.is_read_only().unwrap());
+        assert!(!archive.opened_read_only());
+        assert_eq!(
+            archive.delete(0).unwrap_err().zip(),
+            Some(error::Zip::ReadOnly)
+        );
+        assert_eq!(
+            archive.set_read_only(false).unwrap_err().zip(),
+            Some(error::Zip::ReadOnly)
+        );
+        assert!(archive.flag(archive::ArchiveFlag::ReadOnly).unwrap());
+        archive.close().unwrap();
+    }
+
+    #[test]
+    fn owned_sources() {
+        let tempdir = TempDir::new("test").unwrap();
+        let zip_path = CString::new(tempdir.path().join("file.zip").to_str().unwrap()).unwrap();
+        let shared: Rc<[u8]> = Rc::from(&b"shared"[..]);
+
+        {
+            let file_source: source::Source<source::File> =
+                (&zip_path as &CStr).try_into().unwrap();
+            let mut archive =
+                archive::Archive::open(file_source, [archive::OpenFlag::Create]).unwrap();
+            let vec_source: source::Source<Vec<u8>> = b"vec".to_vec().try_into().unwrap();
+            let boxed_source: source::Source<Box<[u8]>> =
+                b"boxed".to_vec().into_boxed_slice().try_into().unwrap();
+            let shared_source = source::Source::from_owned(shared.clone()).unwrap();
+            archive
+                .add(
+                    CString::new("vec").unwrap(),
+                    vec_source,
+                    file::Encoding::Guess,
+                    false,
+                )
+                .unwrap();
+            archive
+                .add(
+                    CString::new("boxed").unwrap(),
+                    boxed_source,
+                    file::Encoding::Guess,
+                    false,
+                )
+                .unwrap();
+            archive
+                .add(
+                    CString::new("shared").unwrap(),
+                    shared_source,
+                    file::Encoding::Guess,
+                    false,
+                )
+                .unwrap();
+            assert_eq!(Rc::strong_count(&shared), 2);
+            archive.close().unwrap();
+        }
+        assert_eq!(Rc::strong_count(&shared), 1);
+
+        let mut archive = open_read_only(&zip_path);
+        for (index, expected) in ["vec", "boxed", "shared"].iter().enumerate() {
+            let mut buf = String::new();
+            archive
+                .open_index(index as _, [])
+                .unwrap()
+                .read_to_string(&mut buf)
+                .unwrap();
+            assert_eq!(buf, *expected);
+        }
+        archive.close().unwrap();
+    }
+
+    #[test]
+    fn edit_in_place() {
+        let tempdir = TempDir::new("test").unwrap();
+        let zip_path = CString::new(tempdir.path().join("file.zip").to_str().unwrap()).unwrap();
+        let foo = "Lorem ipsum dolor sit amet";
+        let bar = "sed do eiusmod tempor incididunt ut labore et dolore magna aliqua";
+
+        {
+            let file_source: source::Source<source::File> =
+                (&zip_path as &CStr).try_into().unwrap();
+            let mut archive =
+                archive::Archive::open(file_source, [archive::OpenFlag::Create]).unwrap();
+            for (name, content) in [("foo", foo), ("bar", bar)].iter() {
+                let content_source: source::Source<&[u8]> = content.as_bytes().try_into().unwrap();
+                archive
+                    .add(
+                        CString::new(*name).unwrap(),
+                        content_source,
+                        file::Encoding::Guess,
+                        false,
+                    )
+                    .unwrap();
+            }
+            archive.close().unwrap();
+        }
+
+        {
+            let file_source: source::Source<source::File> =
+                (&zip_path as &CStr).try_into().unwrap();
+            let mut archive = archive::Archive::open(file_source, []).unwrap();
+            assert!(!archive.is_read_only().unwrap());
+            archive.delete(0).unwrap();
+            archive
+                .rename(1, CString::new("baz").unwrap(), file::Encoding::Guess)
+                .unwrap();
+            archive.unchange(0).unwrap();
+            archive.delete(0).unwrap();
+            archive
+                .add_dir(CString::new("qux").unwrap(), file::Encoding::Guess)
+                .unwrap();
+            archive.close().unwrap();
+        }
+
+        {
+            let file_source: source::Source<source::File> =
+                (&zip_path as &CStr).try_into().unwrap();
+            let archive =
+                archive::Archive::open(file_source, [archive::OpenFlag::ReadOnly]).unwrap();
+            let entries: Vec<_> = archive
+                .entries([])
+                .map(|entry| {
+                    let entry = entry.unwrap();
+                    (entry.name().to_owned(), entry.is_dir())
+                })
+                .collect();
+            assert_eq!(
+                entries,
+                [
+                    (CString::new("baz").unwrap(), false),
+                    (CString::new("qux/").unwrap(), true)
+                ]
+            );
+            archive.close().unwrap();
+        }
+    }
+
+    #[test]
+    fn per_file_password() {
+        if !file::Encryption::AES256.is_supported(true) {
+            eprintln!("skipping per_file_password, as libzip was built without AES support");
+            return;
+        }
+        let tempdir = TempDir::new("test").unwrap();
+        let zip_path = CString::new(tempdir.path().join("file.zip").to_str().unwrap()).unwrap();
+        let foo = "Lorem ipsum dolor sit amet";
+
+        {
+            let file_source: source::Source<source::File> =
+                (&zip_path as &CStr).try_into().unwrap();
+            let mut archive =
+                archive::Archive::open(file_source, [archive::OpenFlag::Create]).unwrap();
+            let foo_source: source::Source<&[u8]> = foo.as_bytes().try_into().unwrap();
+            let foo_index = archive
+                .add(
+                    CString::new("foo").unwrap(),
+                    foo_source,
+                    file::Encoding::Guess,
+                    false,
+                )
+                .unwrap();
+            archive
+                .set_encryption_with_password(
+                    file::Encryption::AES256,
+                    foo_index,
+                    CString::new("hunter2").unwrap(),
+                )
+                .unwrap();
+            archive.close().unwrap();
+        }
+
+        {
+            let file_source: source::Source<source::File> =
+                (&zip_path as &CStr).try_into().unwrap();
+            let mut archive =
+                archive::Archive::open(file_source, [archive::OpenFlag::ReadOnly]).unwrap();
+            assert!(archive.open_index(0, []).is_err());
+            let mut foo_buf = String::new();
+            archive
+                .open_index_encrypted(0, [], CString::new("hunter2").unwrap())
+                .unwrap()
+                .read_to_string(&mut foo_buf)
+                .unwrap();
+            assert_eq!(foo_buf, foo);
+            archive.close().unwrap();
+        }
+    }
+
+    #[test]
+    fn different_passwords() {
+        let method = if file::Encryption::AES256.is_supported(true) {
+            file::Encryption::AES256
+        } else if file::Encryption::PkWare.is_supported(true) {
+            file::Encryption::PkWare
+        } else {
+            eprintln!("skipping different_passwords, as libzip was built without encryption");
+            return;
+        };
+        let tempdir = TempDir::new("test").unwrap();
+        let zip_path = CString::new(tempdir.path().join("file.zip").to_str().unwrap()).unwrap();
+        let entries = [
+            ("alice", "Lorem ipsum dolor sit amet", "hunter2"),
+            ("bob", "sed do eiusmod tempor", "correct horse"),
+        ];
+        let contents: Vec<_> = entries
+            .iter()
+            .map(|(name, content, _)| (*name, *content))
+            .collect();
+        create_archive(&zip_path, &contents, |archive| {
+            for (index, (_, _, password)) in entries.iter().enumerate() {
+                archive
+                    .set_encryption_with_password(
+                        method,
+                        index as _,
+                        CString::new(*password).unwrap(),
+                    )
+                    .unwrap();
+            }
+        });
+
+        let mut archive = open_read_only(&zip_path);
+        for (index, (_, content, password)) in entries.iter().enumerate() {
+            let mut buf = String::new();
+            archive
+                .open_index_encrypted(index as _, [], CString::new(*password).unwrap())
+                .unwrap()
+                .read_to_string(&mut buf)
+                .unwrap();
+            assert_eq!(buf, *content);
+        }
+        // Each entry only opens with its own password.
+        let (_, _, alice_password) = entries[0];
+        let mut buf = Vec::new();
+        assert!(archive
+            .open_index_encrypted(1, [], CString::new(alice_password).unwrap())
+            .map_or(true, |mut file| file.read_to_end(&mut buf).is_err()));
+        archive.close().unwrap();
+    }
+
+    #[test]
+    fn progress() {
+        let tempdir = TempDir::new("test").unwrap();
+        let zip_path = CString::new(tempdir.path().join("file.zip").to_str().unwrap()).unwrap();
+        let updates = Rc::new(RefCell::new(Vec::new()));
+        let callback_updates = updates.clone();
+        create_archive(&zip_path, &[("foo", "foo"), ("bar", "bar")], |archive| {
+            archive
+                .on_progress(0.01, move |value| callback_updates.borrow_mut().push(value))
+                .unwrap();
+        });
+
+        // The callback is dropped along with the archive.
+        let updates = Rc::try_unwrap(updates).unwrap().into_inner();
+        assert_eq!(updates.last(), Some(&1.0));
+        assert!(updates.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn panicking_callback_drop() {
+        /// Panics when dropped.
+        struct Panicking;
+
+        impl Drop for Panicking {
+            fn drop(&mut self) {
+                panic!("drop");
+            }
+        }
+
+        let tempdir = TempDir::new("test").unwrap();
+        let zip_path = CString::new(tempdir.path().join("file.zip").to_str().unwrap()).unwrap();
+        // Freeing the callbacks runs the panicking Drop, which must not unwind into libzip.
+        create_archive(&zip_path, &[("foo", "foo")], |archive| {
+            let panicking = Panicking;
+            archive
+                .on_progress(0.01, move |_| {
+                    let _ = &panicking;
+                })
+                .unwrap();
+            let panicking = Panicking;
+            archive
+                .on_cancel(move || {
+                    let _ = &panicking;
+                    false
+                })
+                .unwrap();
+        });
+        assert_eq!(open_read_only(&zip_path).len(), 1);
+    }
+
+    #[test]
+    fn cancel_close() {
+        let tempdir = TempDir::new("test").unwrap();
+        let path = tempdir.path().join("file.zip");
+        let zip_path = CString::new(path.to_str().unwrap()).unwrap();
+        let foo = "Lorem ipsum dolor sit amet";
+
+        let file_source: source::Source<source::File> = (&zip_path as &CStr).try_into().unwrap();
+        let mut archive = archive::Archive::open(file_source, [archive::OpenFlag::Create]).unwrap();
+        let foo_source: source::Source<&[u8]> = foo.as_bytes().try_into().unwrap();
+        archive
+            .add(
+                CString::new("foo").unwrap(),
+                foo_source,
+                file::Encoding::Guess,
+                false,
+            )
+            .unwrap();
+        archive.on_cancel(|| true).unwrap();
+        let (archive, error) = archive.close().unwrap_err();
+        assert_eq!(error.zip(), Some(error::Zip::Cancelled));
+        archive.discard();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn function_source() {
+        /// Produces `len` bytes counting up from 0.
+        struct Counter {
+            len: usize,
+            position: usize,
+        }
+
+        impl source::SourceImpl for Counter {
+            fn open(&mut self) -> std::io::Result<()> {
+                self.position = 0;
+                Ok(())
+            }
+
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let read = buf.len().min(self.len - self.position);
+                for (i, byte) in buf[..read].iter_mut().enumerate() {
+                    *byte = (self.position + i) as u8;
+                }
+                self.position += read;
+                Ok(read)
+            }
+
+            fn stat(&mut self) -> std::io::Result<file::Stat> {
+                Ok(file::Stat {
+                    size: Some(self.len as _),
+                    ..Default::default()
+                })
+            }
+        }
+
+        let tempdir = TempDir::new("test").unwrap();
+        let zip_path = CString::new(tempdir.path().join("file.zip").to_str().unwrap()).unwrap();
+        let len = 100_000;
+
+        {
+            let file_source: source::Source<source::File> =
+                (&zip_path as &CStr).try_into().unwrap();
+            let mut archive =
+                archive::Archive::open(file_source, [archive::OpenFlag::Create]).unwrap();
+            let counter_source = source::Source::from_impl(Counter { len, position: 0 }).unwrap();
+            archive
+                .add(
+                    CString::new("counter").unwrap(),
+                    counter_source,
+                    file::Encoding::Guess,
+                    false,
+                )
+                .unwrap();
+            archive.close().unwrap();
+        }
+
+        {
+            let file_source: source::Source<source::File> =
+                (&zip_path as &CStr).try_into().unwrap();
+            let mut archive =
+                archive::Archive::open(file_source, [archive::OpenFlag::ReadOnly]).unwrap();
+            let mut counter_buf = Vec::new();
+            archive
+                .open_index(0, [])
+                .unwrap()
+                .read_to_end(&mut counter_buf)
+                .unwrap();
+            assert_eq!(counter_buf.len(), len);
+            assert!(counter_buf
+                .iter()
+                .enumerate()
+                .all(|(i, byte)| *byte == i as u8));
+            archive.close().unwrap();
+        }
+    }
+
+    #[test]
+    fn panicking_source() {
+        /// Panics when read from and when dropped.
+        struct Panicking;
+
+        impl source::SourceImpl for Panicking {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                panic!("read");
+            }
+        }
+
+        impl Drop for Panicking {
+            fn drop(&mut self) {
+                panic!("drop");
+            }
+        }
+
+        let tempdir = TempDir::new("test").unwrap();
+        let zip_path = CString::new(tempdir.path().join("file.zip").to_str().unwrap()).unwrap();
+        let file_source: source::Source<source::File> = (&zip_path as &CStr).try_into().unwrap();
+        let mut archive = archive::Archive::open(file_source, [archive::OpenFlag::Create]).unwrap();
+        let panicking_source = source::Source::from_impl(Panicking).unwrap();
+        archive
+            .add(
+                CString::new("panicking").unwrap(),
+                panicking_source,
+                file::Encoding::Guess,
+                false,
+            )
+            .unwrap();
+        let (archive, error) = archive.close().unwrap_err();
+        assert!(error.zip().is_some());
+        // Freeing the source runs the panicking Drop, which must not unwind into libzip.
+        archive.discard();
+    }
+
+    #[test]
+    fn overlong_read() {
+        /// Claims to have read more than fits in the buffer.
+        struct Overlong;
+
+        impl source::SourceImpl for Overlong {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                Ok(buf.len() + 1)
+            }
+        }
+
+        let tempdir = TempDir::new("test").unwrap();
+        let zip_path = CString::new(tempdir.path().join("file.zip").to_str().unwrap()).unwrap();
+        let file_source: source::Source<source::File> = (&zip_path as &CStr).try_into().unwrap();
+        let mut archive = archive::Archive::open(file_source, [archive::OpenFlag::Create]).unwrap();
+        let overlong_source = source::Source::from_impl(Overlong).unwrap();
+        archive
+            .add(
+                CString::new("overlong").unwrap(),
+                overlong_source,
+                file::Encoding::Guess,
+                false,
+            )
+            .unwrap();
+        let (archive, error) = archive.close().unwrap_err();
+        assert!(error.zip().is_some());
+        archive.discard();
+    }
+
+    #[test]
+    fn reader_source() {
+        let tempdir = TempDir::new("test").unwrap();
+        let path = tempdir.path().join("file.zip");
+        let zip_path = CString::new(path.to_str().unwrap()).unwrap();
+        let foo = "Lorem ipsum dolor sit amet";
+
+        {
+            let file_source: source::Source<source::File> =
+                (&zip_path as &CStr).try_into().unwrap();
+            let mut archive =
+                archive::Archive::open(file_source, [archive::OpenFlag::Create]).unwrap();
+            let foo_source: source::Source<&[u8]> = foo.as_bytes().try_into().unwrap();
+            archive
+                .add(
+                    CString::new("foo").unwrap(),
+                    foo_source,
+                    file::Encoding::Guess,
+                    false,
+                )
+                .unwrap();
+            archive.close().unwrap();
+        }
+
+        let reader_source =
+            source::Source::from_reader(std::fs::File::open(&path).unwrap()).unwrap();
+        let mut archive =
+            archive::Archive::open(reader_source, [archive::OpenFlag::CheckConsistency]).unwrap();
+        assert!(archive.is_read_only().unwrap());
+        assert_eq!(archive.len(), 1);
+        let mut foo_buf = String::new();
+        archive
+            .open_file(CString::new("foo").unwrap(), [], [])
+            .unwrap()
+            .read_to_string(&mut foo_buf)
+            .unwrap();
+        assert_eq!(foo_buf, foo);
+        archive.close().unwrap();
+    }
+
+    #[test]
+    fn writer_source() {
+        let foo = "Lorem ipsum dolor sit amet";
+
+        let (writer_source, sink) =
+            source::Source::from_writer(std::io::Cursor::new(Vec::new())).unwrap();
+        let mut archive =
+            archive::Archive::open(writer_source, [archive::OpenFlag::Create]).unwrap();
+        let foo_source: source::Source<&[u8]> = foo.as_bytes().try_into().unwrap();
+        archive
+            .add(
+                CString::new("foo").unwrap(),
+                foo_source,
+                file::Encoding::Guess,
+                false,
+            )
+            .unwrap();
+        let (archive, error) = archive.into_bytes().unwrap_err();
+        assert_eq!(error.zip(), Some(error::Zip::OperationNotSupported));
+        archive.unwrap().close().unwrap();
+        let mut cursor = sink.into_inner().unwrap();
+        assert!(cursor.get_ref().starts_with(b"PK"));
+
+        cursor.set_position(0);
+        let reader_source = source::Source::from_reader(cursor).unwrap();
+        let mut archive =
+            archive::Archive::open(reader_source, [archive::OpenFlag::CheckConsistency]).unwrap();
+        let mut foo_buf = String::new();
+        archive
+            .open_index(0, [])
+            .unwrap()
+            .read_to_string(&mut foo_buf)
+            .unwrap();
+        assert_eq!(foo_buf, foo);
+        archive.close().unwrap();
+    }
+
+    #[test]
+    fn in_memory() {
+        let foo = "Lorem ipsum dolor sit amet";
+
+        let mut archive = archive::Archive::create_in_memory().unwrap();
+        let foo_source: source::Source<&[u8]> = foo.as_bytes().try_into().unwrap();
+        archive
+            .add(
+                CString::new("foo").unwrap(),
+                foo_source,
+                file::Encoding::Guess,
+                false,
+            )
+            .unwrap();
+        // A failed close gives the archive back, with its changes still pending.
+        archive.on_cancel(|| true).unwrap();
+        let (archive, error) = archive.into_bytes().unwrap_err();
+        assert_eq!(error.zip(), Some(error::Zip::Cancelled));
+        let mut archive = archive.unwrap();
+        archive.on_cancel(|| false).unwrap();
+        let bytes = archive.into_bytes().unwrap();
+
+        let bytes_source: source::Source<Vec<u8>> = bytes.try_into().unwrap();
+        let mut archive =
+            archive::Archive::open(bytes_source, [archive::OpenFlag::CheckConsistency]).unwrap();
+        let mut foo_buf = String::new();
+        archive
+            .open_index(0, [])
+            .unwrap()
+            .read_to_string(&mut foo_buf)
+            .unwrap();
+        assert_eq!(foo_buf, foo);
+        archive.close().unwrap();
+
+        let archive = archive::Archive::create_in_memory().unwrap();
+        assert_eq!(archive.into_bytes().unwrap(), b"");
+    }
+
+    #[test]
+    fn edit_in_memory() {
+        let foo = "Lorem ipsum dolor sit amet";
+        let bar = "sed do eiusmod tempor incididunt ut labore et dolore magna aliqua";
+
+        let mut archive = archive::Archive::create_in_memory().unwrap();
+        for (name, content) in [("foo", foo), ("bar", bar)].iter() {
+            let content_source: source::Source<&[u8]> = content.as_bytes().try_into().unwrap();
+            archive
+                .add(
+                    CString::new(*name).unwrap(),
+                    content_source,
+                    file::Encoding::Guess,
+                    false,
+                )
+                .unwrap();
+        }
+        let bytes = archive.into_bytes().unwrap();
+
+        let bytes_source: source::Source<Vec<u8>> = bytes.try_into().unwrap();
+        let mut archive = archive::Archive::open(bytes_source, []).unwrap();
+        assert!(!archive.is_read_only().unwrap());
+        archive.delete(0).unwrap();
+        let foo_source: source::Source<&[u8]> = foo.as_bytes().try_into().unwrap();
+        archive.replace(1, foo_source).unwrap();
+        let bytes = archive.into_bytes().unwrap();
+
+        let bytes_source: source::Source<Vec<u8>> = bytes.try_into().unwrap();
+        let mut archive =
+            archive::Archive::open(bytes_source, [archive::OpenFlag::CheckConsistency]).unwrap();
+        assert_eq!(archive.len(), 1);
+        assert_eq!(
+            archive.name(0, []).unwrap(),
+            CString::new("bar").unwrap().as_c_str()
+        );
+        let mut bar_buf = String::new();
+        archive
+            .open_index(0, [])
+            .unwrap()
+            .read_to_string(&mut bar_buf)
+            .unwrap();
+        assert_eq!(bar_buf, foo);
+        archive.close().unwrap();
+    }
+
+    #[test]
+    fn fragments() {
+        let foo = ["Lorem ", "ipsum ", "", "dolor sit amet"];
+        let bar = vec![b"sed do ".to_vec(), b"eiusmod tempor".to_vec()];
+
+        let mut archive = archive::Archive::create_in_memory().unwrap();
+        let fragments: Vec<_> = foo.iter().map(|fragment| fragment.as_bytes()).collect();
+        let foo_source = source::Source::from_fragments(&fragments).unwrap();
+        archive
+            .add(
+                CString::new("foo").unwrap(),
+                foo_source,
+                file::Encoding::Guess,
+                false,
+            )
+            .unwrap();
+        let bar_source = source::Source::from_owned_fragments(bar).unwrap();
+        archive
+            .add(
+                CString::new("bar").unwrap(),
+                bar_source,
+                file::Encoding::Guess,
+                false,
+            )
+            .unwrap();
+        let bytes = archive.into_bytes().unwrap();
+
+        let bytes_source: source::Source<Vec<u8>> = bytes.try_into().unwrap();
+        let mut archive =
+            archive::Archive::open(bytes_source, [archive::OpenFlag::CheckConsistency]).unwrap();
+        for (index, expected) in [
+            (0, "Lorem ipsum dolor sit amet"),
+            (1, "sed do eiusmod tempor"),
+        ]
+        .iter()
+        {
+            let mut buf = String::new();
+            archive
+                .open_index(*index, [])
+                .unwrap()
+                .read_to_string(&mut buf)
+                .unwrap();
+            assert_eq!(buf, *expected);
+        }
+        archive.close().unwrap();
+    }
+
+    #[test]
+    fn typed_extra_fields() {
+        use extra_field::{ExtendedTimestamp, ExtraField, InfoZipUnix};
+        use file::Location::{Both, Central, Local};
+
+        let tempdir = TempDir::new("test").unwrap();
+        let zip_path = CString::new(tempdir.path().join("file.zip").to_str().unwrap()).unwrap();
+        let owner = InfoZipUnix {
+            uid: 1000,
+            gid: 100,
+        };
+        create_archive(&zip_path, &[("foo", "foo")], |archive| {
+            archive
+                .set_typed_extra_field(0, &InfoZipUnix { uid: 0, gid: 0 }, Both)
+                .unwrap();
+            // Setting it again replaces the field rather than adding another one.
+            archive.set_typed_extra_field(0, &owner, Both).unwrap();
+        });
+
+        let archive = open_read_only(&zip_path);
+        assert_eq!(
+            archive.typed_extra_field::<InfoZipUnix>(0, Local).unwrap(),
+            Some(owner)
+        );
+        assert_eq!(
+            archive
+                .typed_extra_field::<InfoZipUnix>(0, Central)
+                .unwrap(),
+            Some(owner)
+        );
+        assert_eq!(
+            archive
+                .extra_fields_count_by_id(0, InfoZipUnix::ID, Central)
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            archive
+                .typed_extra_field::<ExtendedTimestamp>(0, Central)
+                .unwrap(),
+            None
+        );
+        archive.close().unwrap();
+    }
+
+    #[test]
+    fn writer_source_short_writes() {
+        /// Writes at most a few bytes per call.
+        #[derive(Debug)]
+        struct Trickle(std::io::Cursor<Vec<u8>>);
+
+        impl std::io::Write for Trickle {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                let len = buf.len().min(3);
+                self.0.write(&buf[..len])
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                self.0.flush()
+            }
+        }
+
+        impl std::io::Seek for Trickle {
+            fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+                self.0.seek(pos)
+            }
+        }
+
+        let foo = "Lorem ipsum dolor sit amet";
+        let (writer_source, sink) =
+            source::Source::from_writer(Trickle(std::io::Cursor::new(Vec::new()))).unwrap();
+        let mut archive =
+            archive::Archive::open(writer_source, [archive::OpenFlag::Create]).unwrap();
+        let foo_source: source::Source<&[u8]> = foo.as_bytes().try_into().unwrap();
+        archive
+            .add(
+                CString::new("foo").unwrap(),
+                foo_source,
+                file::Encoding::Guess,
+                false,
+            )
+            .unwrap();
+        archive.close().unwrap();
+        let Trickle(mut cursor) = sink.into_inner().unwrap();
+
+        cursor.set_position(0);
+        let reader_source = source::Source::from_reader(cursor).unwrap();
+        let mut archive =
+            archive::Archive::open(reader_source, [archive::OpenFlag::CheckConsistency]).unwrap();
+        let mut foo_buf = String::new();
+        archive
+            .open_index(0, [])
+            .unwrap()
+            .read_to_string(&mut foo_buf)
+            .unwrap();
+        assert_eq!(foo_buf, foo);
+        archive.close().unwrap();
+    }
+
+    #[test]
+    fn writer_source_rollback() {
+        let foo = "Lorem ipsum dolor sit amet";
+        let mut cursor = std::io::Cursor::new(b"header".to_vec());
+        cursor.set_position(6);
+        let (writer_source, sink) = source::Source::from_writer(cursor).unwrap();
+        let mut archive =
+            archive::Archive::open(writer_source, [archive::OpenFlag::Create]).unwrap();
+        let foo_source: source::Source<&[u8]> = foo.as_bytes().try_into().unwrap();
+        archive
+            .add(
+                CString::new("foo").unwrap(),
+                foo_source,
+                file::Encoding::Guess,
+                false,
+            )
+            .unwrap();
+        archive.on_cancel(|| true).unwrap();
+        let (archive, error) = archive.close().unwrap_err();
+        assert_eq!(error.zip(), Some(error::Zip::Cancelled));
+        archive.discard();
+        let cursor = sink.into_inner().unwrap();
+        assert_eq!(cursor.position(), 6);
+        assert!(cursor.get_ref().starts_with(b"header"));
+    }
+
+    #[test]
+    fn extra_field_round_trip() {
+        use extra_field::*;
+
+        let time = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let timestamp = ExtendedTimestamp {
+            mtime: Some(time),
+            atime: None,
+            ctime: Some(time),
+        };
+        assert_eq!(
+            ExtendedTimestamp::decode(&timestamp.encode()),
+            Some(timestamp)
+        );
+        let unix = InfoZipUnix {
+            uid: 1000,
+            gid: 100,
+        };
+        assert_eq!(InfoZipUnix::decode(&unix.encode()), Some(unix));
+        assert_eq!(InfoZipUnix::decode(&[1, 2, 0xe8, 0x03, 1, 100]), Some(unix));
+        let ntfs = Ntfs {
+            mtime: time,
+            atime: time + Duration::from_nanos(100),
+            ctime: UNIX_EPOCH - Duration::from_secs(1),
+        };
+        assert_eq!(Ntfs::decode(&ntfs.encode()), Some(ntfs));
+        // Times past the end of the FILETIME range saturate rather than overflow.
+        let late = Ntfs {
+            mtime: UNIX_EPOCH + Duration::from_secs(1 << 41),
+            ..ntfs
+        };
+        assert_eq!(late.encode()[8..16], u64::MAX.to_le_bytes());
+        let zip64 = Zip64 {
+            size: Some(1 << 32),
+            compressed_size: Some(1 << 33),
+            header_offset: None,
+            disk_start: None,
+        };
+        assert_eq!(
+            Zip64::decode_with(&zip64.encode(), true, true, false, false),
+            Some(zip64)
+        );
+        // A small entry stored past 4 GiB only has its header offset saturated.
+        let offset_only = (5u64 << 32).to_le_bytes();
+        assert_eq!(
+            Zip64::decode_with(&offset_only, false, false, true, false),
+            Some(Zip64 {
+                size: None,
+                compressed_size: None,
+                header_offset: Some(5 << 32),
+                disk_start: None,
+            })
+        );
+        assert_eq!(
+            Zip64::decode_with(&offset_only, true, true, true, false),
+            None
+        );
+        let path = UnicodePath {
+            name_crc: 0xdeadbeef,
+            name: "f\u{f6}\u{f6}".to_owned(),
+        };
+        assert_eq!(UnicodePath::decode(&path.encode()), Some(path));
+        let aes = WinZipAes {
+            version: 2,
+            strength: 3,
+            compression_method: ffi::ZIP_CM_DEFLATE as _,
+        };
+        assert_eq!(aes.encode(), [2, 0, b'A', b'E', 3, 8, 0]);
+        assert_eq!(WinZipAes::decode(&aes.encode()), Some(aes));
+    }
 }
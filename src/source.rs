@@ -1,12 +1,21 @@
 use crate::error::ZipErrorT;
 use crate::ffi;
+use crate::file::Stat;
 use crate::Error;
 use crate::Result;
+use std::any::Any;
+use std::cell::RefCell;
 use std::convert::{TryFrom, TryInto};
 use std::ffi::{CStr, CString};
+use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::marker::PhantomData;
+use std::mem::size_of;
+use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
 use std::path::Path;
-use std::ptr::null_mut;
+use std::ptr::{self, null_mut};
+use std::rc::Rc;
+use std::slice;
 
 /// A simple marker enum, used to indicate that the source holds an open file handle.
 pub enum File {}
@@ -15,19 +24,623 @@ pub enum File {}
 #[derive(Debug)]
 pub struct Source<T> {
     handle: *mut ffi::zip_source_t,
+    /// Owned data that the handle reads from, which has to outlive it.
+    buffer: Option<Box<dyn Any>>,
+    /// Whether an archive written into the source can be read back from it.
+    readable: bool,
     phantom: PhantomData<T>,
 }
 
 impl<T> Source<T> {
     /// Indicate that the ownership has been taken by zip_open_from_source, zip_file_add, or
     /// zip_file_replace, and therefore shouldn't be freed.
-    pub(crate) fn taken(mut self) {
+    /// Returns the owned data backing the source, which the taker has to keep alive for as long
+    /// as libzip may read from the source.
+    pub(crate) fn taken(mut self) -> Option<Box<dyn Any>> {
         self.handle = null_mut();
+        self.buffer.take()
     }
 
     pub(crate) fn handle_mut(&mut self) -> *mut ffi::zip_source_t {
         self.handle
     }
+
+    pub(crate) fn is_readable(&self) -> bool {
+        self.readable
+    }
+}
+
+impl<B> Source<B>
+where
+    B: AsRef<[u8]> + 'static,
+{
+    /// Create a source that owns its data, such as a `Vec<u8>` or a reference-counted buffer.
+    /// The data is kept alive for as long as libzip needs it, so it may be handed to
+    /// [Archive::add](crate::archive::Archive::add) and forgotten about.
+    pub fn from_owned(buffer: B) -> Result<Source<B>> {
+        // Boxing keeps the data at a stable address, even if B stores it inline.
+        let buffer = Box::new(buffer);
+        let data = (*buffer).as_ref();
+        let mut error = ZipErrorT::default();
+        let handle = unsafe {
+            ffi::zip_source_buffer_create(data.as_ptr() as _, data.len() as _, 0, &mut *error)
+        };
+        if handle.is_null() {
+            Err(error.into())
+        } else {
+            Ok(Source {
+                handle,
+                buffer: Some(buffer),
+                readable: true,
+                phantom: PhantomData,
+            })
+        }
+    }
+}
+
+impl<B> Source<Vec<B>>
+where
+    B: AsRef<[u8]> + 'static,
+{
+    /// Create a source that owns the fragments of its data, which is read as if they were
+    /// concatenated, without copying them.
+    /// Like [Source::from_owned], the fragments are kept alive for as long as libzip needs them.
+    pub fn from_owned_fragments(fragments: Vec<B>) -> Result<Source<Vec<B>>> {
+        // The fragments live on the heap, so moving the vector into the box does not move them.
+        let fragments = Box::new(fragments);
+        let handle = fragments_source(fragments.iter().map(AsRef::as_ref))?;
+        Ok(Source {
+            handle,
+            buffer: Some(fragments),
+            readable: true,
+            phantom: PhantomData,
+        })
+    }
+}
+
+/// Create a buffer source reading from fragments, which have to outlive it.
+fn fragments_source<'a, I>(fragments: I) -> Result<*mut ffi::zip_source_t>
+where
+    I: Iterator<Item = &'a [u8]>,
+{
+    // libzip copies the fragment descriptors, but not the data they point to.
+    let fragments: Vec<_> = fragments
+        .map(|fragment| ffi::zip_buffer_fragment_t {
+            data: fragment.as_ptr() as _,
+            length: fragment.len() as _,
+        })
+        .collect();
+    let mut error = ZipErrorT::default();
+    let handle = unsafe {
+        ffi::zip_source_buffer_fragment_create(
+            fragments.as_ptr(),
+            fragments.len() as _,
+            0,
+            &mut *error,
+        )
+    };
+    if handle.is_null() {
+        Err(error.into())
+    } else {
+        Ok(handle)
+    }
+}
+
+impl TryFrom<Vec<u8>> for Source<Vec<u8>> {
+    type Error = Error;
+
+    fn try_from(buffer: Vec<u8>) -> Result<Source<Vec<u8>>> {
+        Source::from_owned(buffer)
+    }
+}
+
+impl TryFrom<Box<[u8]>> for Source<Box<[u8]>> {
+    type Error = Error;
+
+    fn try_from(buffer: Box<[u8]>) -> Result<Source<Box<[u8]>>> {
+        Source::from_owned(buffer)
+    }
+}
+
+/// A source implemented in Rust, for use with [Source::from_impl].
+/// libzip drives the source through these methods: reading an entry from it or an archive opened
+/// from it calls [open](SourceImpl::open), then [read](SourceImpl::read) until it returns 0, then
+/// [close](SourceImpl::close).  Writing an archive into it calls
+/// [begin_write](SourceImpl::begin_write), [write](SourceImpl::write) and finally
+/// [commit_write](SourceImpl::commit_write), or [rollback_write](SourceImpl::rollback_write) on
+/// failure.
+/// Only [read](SourceImpl::read) is required; the other methods default to doing nothing or to
+/// failing with [io::ErrorKind::Unsupported].
+pub trait SourceImpl {
+    /// Whether [seek](SourceImpl::seek) and [tell](SourceImpl::tell) are implemented, which is
+    /// required to open an archive from the source.
+    const SEEKABLE: bool = false;
+
+    /// Whether the write methods are implemented, which is required to write an archive into
+    /// the source.  Writable sources must also be seekable.
+    const WRITABLE: bool = false;
+
+    /// Prepare for reading from the start.
+    fn open(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Read into the buffer, returning the number of bytes read, or 0 at the end of the data.
+    /// A count larger than the buffer is an error, rather than being passed on to libzip.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+
+    /// Reading is done.
+    fn close(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Information about the data, most importantly its size.  The name is ignored.
+    fn stat(&mut self) -> io::Result<Stat> {
+        Ok(Stat::default())
+    }
+
+    /// Move the read position, returning the new position.
+    fn seek(&mut self, _pos: SeekFrom) -> io::Result<u64> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// The read position.
+    fn tell(&mut self) -> io::Result<u64> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// Prepare for writing new data, which replaces the current data once committed.
+    fn begin_write(&mut self) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// Write the buffer, returning the number of bytes written.
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// Make the written data the current data.
+    fn commit_write(&mut self) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// Discard the written data, keeping the current data.
+    fn rollback_write(&mut self) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// Move the write position, returning the new position.
+    fn seek_write(&mut self, _pos: SeekFrom) -> io::Result<u64> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// The write position.
+    fn tell_write(&mut self) -> io::Result<u64> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// Remove the data, which libzip does when closing an archive without entries.
+    fn remove(&mut self) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+impl<T> Source<T>
+where
+    T: SourceImpl,
+{
+    /// Create a source that calls into the given implementation.
+    pub fn from_impl(source: T) -> Result<Source<T>> {
+        let state = Box::into_raw(Box::new(FunctionState {
+            source,
+            error: ZipErrorT::default(),
+        }));
+        let mut error = ZipErrorT::default();
+        let handle = unsafe {
+            ffi::zip_source_function_create(Some(function_callback::<T>), state as _, &mut *error)
+        };
+        if handle.is_null() {
+            unsafe { drop(Box::from_raw(state)) };
+            Err(error.into())
+        } else {
+            Ok(Source {
+                handle,
+                buffer: None,
+                readable: true,
+                phantom: PhantomData,
+            })
+        }
+    }
+}
+
+/// The user data of a function source, owned by libzip until it sends ZIP_SOURCE_FREE.
+struct FunctionState<T> {
+    source: T,
+    /// The last error, reported to libzip through ZIP_SOURCE_ERROR.
+    error: ZipErrorT<ffi::zip_error_t>,
+}
+
+/// A failed command: the libzip error code to report, and the error that caused it.
+type CommandError = (u32, io::Error);
+
+/// Convert a seek command's arguments into a [SeekFrom].
+unsafe fn seek_from(data: *mut c_void, len: u64) -> std::result::Result<SeekFrom, CommandError> {
+    let invalid = || (ffi::ZIP_ER_INVAL, io::ErrorKind::InvalidInput.into());
+    if data.is_null() || len < size_of::<ffi::zip_source_args_seek_t>() as u64 {
+        return Err(invalid());
+    }
+    let args = &*(data as *const ffi::zip_source_args_seek_t);
+    match args.whence as u32 {
+        ffi::SEEK_SET if args.offset >= 0 => Ok(SeekFrom::Start(args.offset as _)),
+        ffi::SEEK_CUR => Ok(SeekFrom::Current(args.offset)),
+        ffi::SEEK_END => Ok(SeekFrom::End(args.offset)),
+        _ => Err(invalid()),
+    }
+}
+
+/// The bit of a command in a ZIP_SOURCE_SUPPORTS bitmap.
+const fn command_bit(command: ffi::zip_source_cmd) -> i64 {
+    1 << command
+}
+
+impl<T> FunctionState<T>
+where
+    T: SourceImpl,
+{
+    /// The bitmap of supported commands, reported through ZIP_SOURCE_SUPPORTS.
+    const SUPPORTS: i64 = {
+        let mut bitmap = command_bit(ffi::zip_source_cmd_ZIP_SOURCE_OPEN)
+            | command_bit(ffi::zip_source_cmd_ZIP_SOURCE_READ)
+            | command_bit(ffi::zip_source_cmd_ZIP_SOURCE_CLOSE)
+            | command_bit(ffi::zip_source_cmd_ZIP_SOURCE_STAT)
+            | command_bit(ffi::zip_source_cmd_ZIP_SOURCE_ERROR)
+            | command_bit(ffi::zip_source_cmd_ZIP_SOURCE_FREE)
+            | command_bit(ffi::zip_source_cmd_ZIP_SOURCE_SUPPORTS);
+        if T::SEEKABLE || T::WRITABLE {
+            bitmap |= command_bit(ffi::zip_source_cmd_ZIP_SOURCE_SEEK)
+                | command_bit(ffi::zip_source_cmd_ZIP_SOURCE_TELL);
+        }
+        if T::WRITABLE {
+            bitmap |= command_bit(ffi::zip_source_cmd_ZIP_SOURCE_BEGIN_WRITE)
+                | command_bit(ffi::zip_source_cmd_ZIP_SOURCE_WRITE)
+                | command_bit(ffi::zip_source_cmd_ZIP_SOURCE_COMMIT_WRITE)
+                | command_bit(ffi::zip_source_cmd_ZIP_SOURCE_ROLLBACK_WRITE)
+                | command_bit(ffi::zip_source_cmd_ZIP_SOURCE_SEEK_WRITE)
+                | command_bit(ffi::zip_source_cmd_ZIP_SOURCE_TELL_WRITE)
+                | command_bit(ffi::zip_source_cmd_ZIP_SOURCE_REMOVE);
+        }
+        bitmap
+    };
+
+    /// Run a single command, other than ZIP_SOURCE_FREE.
+    unsafe fn command(
+        &mut self,
+        data: *mut c_void,
+        len: u64,
+        command: ffi::zip_source_cmd,
+    ) -> std::result::Result<i64, CommandError> {
+        let source = &mut self.source;
+        match command {
+            ffi::zip_source_cmd_ZIP_SOURCE_SUPPORTS => Ok(Self::SUPPORTS),
+            ffi::zip_source_cmd_ZIP_SOURCE_ERROR => {
+                Ok(ffi::zip_error_to_data(&*self.error, data, len))
+            }
+            ffi::zip_source_cmd_ZIP_SOURCE_OPEN => {
+                source.open().map(|()| 0).map_err(|e| (ffi::ZIP_ER_OPEN, e))
+            }
+            ffi::zip_source_cmd_ZIP_SOURCE_READ => {
+                // The buffer may be uninitialised, which safe code must not be shown.
+                let buf = if len == 0 {
+                    &mut []
+                } else {
+                    ptr::write_bytes(data as *mut u8, 0, len as _);
+                    slice::from_raw_parts_mut(data as *mut u8, len as _)
+                };
+                let capacity = buf.len();
+                let read = source.read(buf).map_err(|e| (ffi::ZIP_ER_READ, e))?;
+                // libzip trusts the count, so a larger one would have it read past the buffer.
+                if read > capacity {
+                    return Err((
+                        ffi::ZIP_ER_INTERNAL,
+                        io::Error::other("read more bytes than the buffer holds"),
+                    ));
+                }
+                Ok(read as _)
+            }
+            ffi::zip_source_cmd_ZIP_SOURCE_CLOSE => source
+                .close()
+                .map(|()| 0)
+                .map_err(|e| (ffi::ZIP_ER_CLOSE, e)),
+            ffi::zip_source_cmd_ZIP_SOURCE_STAT => {
+                if data.is_null() || len < size_of::<ffi::zip_stat_t>() as u64 {
+                    return Err((ffi::ZIP_ER_INVAL, io::ErrorKind::InvalidInput.into()));
+                }
+                let stat = source.stat().map_err(|e| (ffi::ZIP_ER_READ, e))?;
+                stat.to_raw(&mut *(data as *mut ffi::zip_stat_t));
+                Ok(size_of::<ffi::zip_stat_t>() as _)
+            }
+            ffi::zip_source_cmd_ZIP_SOURCE_SEEK => source
+                .seek(seek_from(data, len)?)
+                .map(|_| 0)
+                .map_err(|e| (ffi::ZIP_ER_SEEK, e)),
+            ffi::zip_source_cmd_ZIP_SOURCE_TELL => source
+                .tell()
+                .map(|position| position as _)
+                .map_err(|e| (ffi::ZIP_ER_TELL, e)),
+            ffi::zip_source_cmd_ZIP_SOURCE_BEGIN_WRITE => source
+                .begin_write()
+                .map(|()| 0)
+                .map_err(|e| (ffi::ZIP_ER_OPEN, e)),
+            ffi::zip_source_cmd_ZIP_SOURCE_WRITE => {
+                let buf = if len == 0 {
+                    &[]
+                } else {
+                    slice::from_raw_parts(data as *const u8, len as _)
+                };
+                source
+                    .write(buf)
+                    .map(|written| written as _)
+                    .map_err(|e| (ffi::ZIP_ER_WRITE, e))
+            }
+            ffi::zip_source_cmd_ZIP_SOURCE_COMMIT_WRITE => source
+                .commit_write()
+                .map(|()| 0)
+                .map_err(|e| (ffi::ZIP_ER_WRITE, e)),
+            ffi::zip_source_cmd_ZIP_SOURCE_ROLLBACK_WRITE => source
+                .rollback_write()
+                .map(|()| 0)
+                .map_err(|e| (ffi::ZIP_ER_WRITE, e)),
+            ffi::zip_source_cmd_ZIP_SOURCE_SEEK_WRITE => source
+                .seek_write(seek_from(data, len)?)
+                .map(|_| 0)
+                .map_err(|e| (ffi::ZIP_ER_SEEK, e)),
+            ffi::zip_source_cmd_ZIP_SOURCE_TELL_WRITE => source
+                .tell_write()
+                .map(|position| position as _)
+                .map_err(|e| (ffi::ZIP_ER_TELL, e)),
+            ffi::zip_source_cmd_ZIP_SOURCE_REMOVE => source
+                .remove()
+                .map(|()| 0)
+                .map_err(|e| (ffi::ZIP_ER_REMOVE, e)),
+            _ => Err((ffi::ZIP_ER_OPNOTSUPP, io::ErrorKind::Unsupported.into())),
+        }
+    }
+
+    fn set_error(&mut self, code: u32, error: &io::Error) {
+        let code = if error.kind() == io::ErrorKind::Unsupported {
+            ffi::ZIP_ER_OPNOTSUPP
+        } else {
+            code
+        };
+        let system = error.raw_os_error().unwrap_or(0);
+        unsafe { ffi::zip_error_set(&mut *self.error, code as _, system) };
+    }
+}
+
+unsafe extern "C" fn function_callback<T>(
+    state: *mut c_void,
+    data: *mut c_void,
+    len: u64,
+    command: ffi::zip_source_cmd,
+) -> i64
+where
+    T: SourceImpl,
+{
+    let state = state as *mut FunctionState<T>;
+    // Unwinding into libzip is undefined behavior, so panics are caught.
+    if command == ffi::zip_source_cmd_ZIP_SOURCE_FREE {
+        // The state is gone either way, so there is nowhere left to report a panic to.
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(state))));
+        return 0;
+    }
+    let state = &mut *state;
+    // A panic in any other command becomes an internal error.
+    let result = panic::catch_unwind(AssertUnwindSafe(|| state.command(data, len, command)))
+        .unwrap_or_else(|_| Err((ffi::ZIP_ER_INTERNAL, io::ErrorKind::Other.into())));
+    match result {
+        Ok(value) => value,
+        Err((code, error)) => {
+            state.set_error(code, &error);
+            -1
+        }
+    }
+}
+
+/// Adapts a [Read] + [Seek] stream into a [SourceImpl], as created by [Source::from_reader].
+#[derive(Debug)]
+pub struct Reader<R> {
+    reader: R,
+}
+
+impl<R> SourceImpl for Reader<R>
+where
+    R: Read + Seek,
+{
+    const SEEKABLE: bool = true;
+
+    fn open(&mut self) -> io::Result<()> {
+        self.reader.seek(SeekFrom::Start(0)).map(|_| ())
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.reader.read(buf) {
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                result => return result,
+            }
+        }
+    }
+
+    fn stat(&mut self) -> io::Result<Stat> {
+        // The size is found by seeking to the end, so put the position back afterwards.
+        let position = self.reader.stream_position()?;
+        let size = self.reader.seek(SeekFrom::End(0))?;
+        self.reader.seek(SeekFrom::Start(position))?;
+        Ok(Stat {
+            size: Some(size),
+            ..Stat::default()
+        })
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.reader.seek(pos)
+    }
+
+    fn tell(&mut self) -> io::Result<u64> {
+        self.reader.stream_position()
+    }
+}
+
+impl<R> Source<Reader<R>>
+where
+    R: Read + Seek,
+{
+    /// Create a source that reads from a stream, such as a [std::fs::File] or an
+    /// [io::Cursor].
+    /// The source is seekable, so an archive can be opened from it.
+    pub fn from_reader(reader: R) -> Result<Source<Reader<R>>> {
+        Source::from_impl(Reader { reader })
+    }
+}
+
+/// Adapts a [Write] + [Seek] sink into a writable [SourceImpl], as created by
+/// [Source::from_writer].
+/// The archive is written starting at the sink's position when writing begins.  Reading from the
+/// source yields no data, so an archive opened from it starts out empty.
+#[derive(Debug)]
+pub struct Writer<W> {
+    writer: Rc<RefCell<W>>,
+    /// The position of the sink when writing began, which libzip sees as offset 0.
+    start: u64,
+}
+
+impl<W> SourceImpl for Writer<W>
+where
+    W: Write + Seek,
+{
+    const SEEKABLE: bool = true;
+    const WRITABLE: bool = true;
+
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Ok(0)
+    }
+
+    fn stat(&mut self) -> io::Result<Stat> {
+        Ok(Stat {
+            size: Some(0),
+            ..Stat::default()
+        })
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match pos {
+            SeekFrom::Start(0) | SeekFrom::Current(0) | SeekFrom::End(0) => Ok(0),
+            _ => Err(io::ErrorKind::InvalidInput.into()),
+        }
+    }
+
+    fn tell(&mut self) -> io::Result<u64> {
+        Ok(0)
+    }
+
+    fn begin_write(&mut self) -> io::Result<()> {
+        self.start = self.writer.borrow_mut().stream_position()?;
+        Ok(())
+    }
+
+    /// libzip treats short writes as failures, so the whole buffer is written.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.borrow_mut().write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn commit_write(&mut self) -> io::Result<()> {
+        self.writer.borrow_mut().flush()
+    }
+
+    /// The sink cannot be truncated, so it is only moved back to where writing began.
+    fn rollback_write(&mut self) -> io::Result<()> {
+        self.writer
+            .borrow_mut()
+            .seek(SeekFrom::Start(self.start))
+            .map(|_| ())
+    }
+
+    fn seek_write(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(offset) => SeekFrom::Start(self.start + offset),
+            pos => pos,
+        };
+        let position = self.writer.borrow_mut().seek(pos)?;
+        position
+            .checked_sub(self.start)
+            .ok_or_else(|| io::ErrorKind::InvalidInput.into())
+    }
+
+    fn tell_write(&mut self) -> io::Result<u64> {
+        Ok(self.writer.borrow_mut().stream_position()? - self.start)
+    }
+
+    /// Nothing can be removed from the sink, so an archive without entries is left empty.
+    fn remove(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Gives back the sink of a [Source::from_writer] once libzip is done with it.
+#[derive(Debug)]
+pub struct Sink<W> {
+    writer: Rc<RefCell<W>>,
+}
+
+impl<W> Sink<W> {
+    /// Take back the sink, which is possible once the archive written into it has been closed or
+    /// discarded, or the source was dropped without being used.
+    /// Otherwise the sink is still in use, and self is returned.
+    pub fn into_inner(self) -> std::result::Result<W, Sink<W>> {
+        Rc::try_unwrap(self.writer)
+            .map(RefCell::into_inner)
+            .map_err(|writer| Sink { writer })
+    }
+}
+
+impl<W> Source<Writer<W>>
+where
+    W: Write + Seek,
+{
+    /// Create a source that an archive can be written into, such as a [std::fs::File] or an
+    /// [io::Cursor], along with a [Sink] to take back the writer afterwards.
+    /// Open an archive from the source with
+    /// [OpenFlag::Create](crate::archive::OpenFlag::Create) and close it to write the archive.
+    ///
+    /// If closing the archive fails or is cancelled, the sink is moved back to the position
+    /// where writing began, but anything already written after it stays there, as a [Write]
+    /// cannot be truncated.  Closing again overwrites it, but may leave stale bytes after the
+    /// end of the archive.  Failing to move the sink back makes closing fail with the seek
+    /// error instead.
+    pub fn from_writer(writer: W) -> Result<(Source<Writer<W>>, Sink<W>)> {
+        let writer = Rc::new(RefCell::new(writer));
+        let mut source = Source::from_impl(Writer {
+            writer: writer.clone(),
+            start: 0,
+        })?;
+        source.readable = false;
+        Ok((source, Sink { writer }))
+    }
+}
+
+impl<'a> Source<&'a [u8]> {
+    /// Create a source that borrows the fragments of its data, which is read as if they were
+    /// concatenated, without copying them.
+    pub fn from_fragments(fragments: &[&'a [u8]]) -> Result<Source<&'a [u8]>> {
+        let handle = fragments_source(fragments.iter().copied())?;
+        Ok(Source {
+            handle,
+            buffer: None,
+            readable: true,
+            phantom: PhantomData,
+        })
+    }
 }
 
 impl<'a> TryFrom<&'a [u8]> for Source<&'a [u8]> {
@@ -43,6 +656,8 @@ impl<'a> TryFrom<&'a [u8]> for Source<&'a [u8]> {
         } else {
             Ok(Source {
                 handle,
+                buffer: None,
+                readable: true,
                 phantom: PhantomData,
             })
         }
@@ -60,6 +675,8 @@ impl TryFrom<&CStr> for Source<File> {
         } else {
             Ok(Source {
                 handle,
+                buffer: None,
+                readable: true,
                 phantom: PhantomData,
             })
         }